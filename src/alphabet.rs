@@ -0,0 +1,191 @@
+/// Standard nucleotides
+const DNA: &[u8] = b"ACGTN";
+
+/// Standard nucleotides with uracil
+const NUCLEOTIDE: &[u8] = b"ACGTUN";
+
+/// IUPAC ambiguity codes and gap characters
+const IUPAC: &[u8] = b"ACGTNRYSWKMBDHV-.";
+
+/// Ribonucleotides
+const RNA: &[u8] = b"ACGUN";
+
+/// Amino acids including ambiguity codes, stops, and gaps
+const PROTEIN: &[u8] = b"ACDEFGHIKLMNPQRSTVWYBZXJUO*-";
+
+/// A set of bytes which are allowed within a sequence.
+///
+/// Each alphabet also defines the byte which invalid characters are
+/// replaced with when fixing a sequence (e.g. 'N' for nucleotides and
+/// 'X' for proteins).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alphabet {
+    valid: [bool; 256],
+    unknown: u8,
+}
+impl Alphabet {
+    /// Builds a case-insensitive alphabet from its uppercase characters
+    fn from_upper(bytes: &[u8], unknown: u8) -> Self {
+        let mut valid = [false; 256];
+        for b in bytes {
+            valid[b.to_ascii_uppercase() as usize] = true;
+            valid[b.to_ascii_lowercase() as usize] = true;
+        }
+        Self { valid, unknown }
+    }
+
+    /// # Usage
+    /// DNA nucleotides (`ACGTN`) in either case.
+    ///
+    /// ```
+    /// let alphabet = fxread::Alphabet::dna();
+    /// assert!(alphabet.validate(b"ACGTNacgtn"));
+    /// assert!(!alphabet.validate(b"ACGU"));
+    /// ```
+    #[must_use]
+    pub fn dna() -> Self {
+        Self::from_upper(DNA, b'N')
+    }
+
+    /// # Usage
+    /// DNA or RNA nucleotides (`ACGTUN`) in either case.
+    ///
+    /// This is the alphabet used by [`Record::valid`](crate::Record::valid).
+    /// ```
+    /// let alphabet = fxread::Alphabet::nucleotide();
+    /// assert!(alphabet.validate(b"ACGTU"));
+    /// ```
+    #[must_use]
+    pub fn nucleotide() -> Self {
+        Self::from_upper(NUCLEOTIDE, b'N')
+    }
+
+    /// # Usage
+    /// DNA nucleotides with IUPAC ambiguity codes (`RYSWKMBDHV`)
+    /// and gap characters (`-` and `.`) in either case.
+    ///
+    /// ```
+    /// let alphabet = fxread::Alphabet::dna_iupac();
+    /// assert!(alphabet.validate(b"ACGTRYKM-.n"));
+    /// assert!(!alphabet.validate(b"ACGU"));
+    /// ```
+    #[must_use]
+    pub fn dna_iupac() -> Self {
+        Self::from_upper(IUPAC, b'N')
+    }
+
+    /// # Usage
+    /// RNA nucleotides (`ACGUN`) in either case.
+    ///
+    /// ```
+    /// let alphabet = fxread::Alphabet::rna();
+    /// assert!(alphabet.validate(b"ACGUN"));
+    /// assert!(!alphabet.validate(b"ACGT"));
+    /// ```
+    #[must_use]
+    pub fn rna() -> Self {
+        Self::from_upper(RNA, b'N')
+    }
+
+    /// # Usage
+    /// Amino acids including the ambiguity codes `BZXJ`, selenocysteine
+    /// (`U`), pyrrolysine (`O`), stops (`*`) and gaps (`-`) in either case.
+    ///
+    /// ```
+    /// let alphabet = fxread::Alphabet::protein();
+    /// assert!(alphabet.validate(b"MKWVTFISLL*"));
+    /// assert!(!alphabet.validate(b"MKW1"));
+    /// ```
+    #[must_use]
+    pub fn protein() -> Self {
+        Self::from_upper(PROTEIN, b'X')
+    }
+
+    /// # Usage
+    /// A custom alphabet of exactly the provided bytes (case-sensitive)
+    /// with `unknown` used to replace invalid characters.
+    ///
+    /// ```
+    /// let alphabet = fxread::Alphabet::custom(b"01", b'0');
+    /// assert!(alphabet.validate(b"0110"));
+    /// assert!(!alphabet.validate(b"012"));
+    /// ```
+    #[must_use]
+    pub fn custom(bytes: &[u8], unknown: u8) -> Self {
+        let mut valid = [false; 256];
+        for b in bytes {
+            valid[*b as usize] = true;
+        }
+        Self { valid, unknown }
+    }
+
+    /// Checks if a byte is part of the alphabet
+    #[must_use]
+    pub fn contains(&self, byte: u8) -> bool {
+        self.valid[byte as usize]
+    }
+
+    /// Returns the byte used to replace invalid characters
+    #[must_use]
+    pub fn unknown(&self) -> u8 {
+        self.unknown
+    }
+
+    /// Checks if every byte of a sequence is part of the alphabet
+    #[must_use]
+    pub fn validate(&self, seq: &[u8]) -> bool {
+        seq.iter().all(|b| self.contains(*b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Alphabet;
+
+    #[test]
+    fn dna() {
+        let alphabet = Alphabet::dna();
+        assert!(alphabet.validate(b"ACGTNacgtn"));
+        assert!(!alphabet.validate(b"ACGTU"));
+        assert!(!alphabet.validate(b"ACGTR"));
+        assert_eq!(alphabet.unknown(), b'N');
+    }
+
+    #[test]
+    fn iupac() {
+        let alphabet = Alphabet::dna_iupac();
+        assert!(alphabet.validate(b"ACGTNRYSWKMBDHV-."));
+        assert!(alphabet.validate(b"acgtnryswkmbdhv"));
+        assert!(!alphabet.validate(b"ACGTU"));
+        assert!(!alphabet.validate(b"E"));
+    }
+
+    #[test]
+    fn rna() {
+        let alphabet = Alphabet::rna();
+        assert!(alphabet.validate(b"ACGUNacgun"));
+        assert!(!alphabet.validate(b"T"));
+    }
+
+    #[test]
+    fn protein() {
+        let alphabet = Alphabet::protein();
+        assert!(alphabet.validate(b"ACDEFGHIKLMNPQRSTVWYBZXJUO*-"));
+        assert!(alphabet.validate(b"acdefghiklmnpqrstvwy"));
+        assert!(!alphabet.validate(b"MK1"));
+        assert_eq!(alphabet.unknown(), b'X');
+    }
+
+    #[test]
+    fn custom_is_case_sensitive() {
+        let alphabet = Alphabet::custom(b"AC", b'A');
+        assert!(alphabet.contains(b'A'));
+        assert!(!alphabet.contains(b'a'));
+        assert!(!alphabet.validate(b"ACG"));
+    }
+
+    #[test]
+    fn empty_sequence() {
+        assert!(Alphabet::dna().validate(b""));
+    }
+}
@@ -0,0 +1,529 @@
+use super::fastq::Strictness;
+use super::parse::{fasta_record, fastq_record, strip_carriage_returns};
+use super::record::Record;
+use anyhow::{anyhow, Result};
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use futures_core::Stream;
+use memchr::memchr;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Mirrors [`std::io::BufRead::read_until`] over an [`AsyncBufRead`].
+///
+/// The number of bytes read so far is kept in `read` so the call can
+/// be resumed after returning [`Poll::Pending`].
+fn poll_read_until<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    delim: u8,
+    bytes: &mut Vec<u8>,
+    read: &mut usize,
+) -> Poll<io::Result<usize>> {
+    loop {
+        let (done, used) = {
+            let available = ready!(Pin::new(&mut *reader).poll_fill_buf(cx))?;
+            match memchr(delim, available) {
+                Some(idx) => {
+                    bytes.extend_from_slice(&available[..=idx]);
+                    (true, idx + 1)
+                }
+                None => {
+                    bytes.extend_from_slice(available);
+                    (false, available.len())
+                }
+            }
+        };
+        Pin::new(&mut *reader).consume(used);
+        *read += used;
+        if done || used == 0 {
+            return Poll::Ready(Ok(std::mem::take(read)));
+        }
+    }
+}
+
+/// The state of a partially read record which is carried
+/// across calls to `poll_next`.
+struct RecordState {
+    bytes: Vec<u8>,
+    /// The sizes of each line read so far
+    lines: [usize; 4],
+    num_lines: usize,
+    partial: usize,
+    /// Whether all lines of the record have been read
+    complete: bool,
+}
+impl RecordState {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::with_capacity(300),
+            lines: [0; 4],
+            num_lines: 0,
+            partial: 0,
+            complete: false,
+        }
+    }
+
+    /// Takes the bytes and line sizes of the record which was read
+    fn take(&mut self) -> (Vec<u8>, [usize; 4], usize) {
+        let bytes = std::mem::replace(&mut self.bytes, Vec::with_capacity(300));
+        let num_lines = std::mem::take(&mut self.num_lines);
+        self.partial = 0;
+        self.complete = false;
+        (bytes, self.lines, num_lines)
+    }
+
+    /// Reads up to `num_lines` lines of a record like
+    /// [`read_lines`](crate::parse::read_lines) and returns whether the
+    /// input is exhausted after lines which are only whitespace.
+    ///
+    /// An I/O error while reading discards the partial record and is
+    /// returned rather than treated as the end of the input.
+    ///
+    /// The lines are assembled into a record by the same functions as
+    /// [`FastaBytes`](crate::fasta::FastaBytes) and
+    /// [`FastqBytes`](crate::fastq::FastqBytes).
+    fn poll_lines<R: AsyncBufRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+        cx: &mut Context<'_>,
+        num_lines: usize,
+    ) -> Poll<io::Result<bool>> {
+        while !self.complete {
+            let polled = poll_read_until(reader, cx, b'\n', &mut self.bytes, &mut self.partial);
+            let mut size = match ready!(polled) {
+                Err(why) => {
+                    self.take();
+                    return Poll::Ready(Err(why));
+                }
+                Ok(x) => x,
+            };
+            if size > 0 {
+                if self.bytes.last() != Some(&b'\n') {
+                    self.bytes.push(b'\n');
+                    size += 1;
+                }
+                self.lines[self.num_lines] = size;
+                self.num_lines += 1;
+            }
+            if size == 0 || self.num_lines == num_lines {
+                strip_carriage_returns(&mut self.bytes, 0, &mut self.lines[..self.num_lines]);
+                self.complete = true;
+            }
+        }
+        if !self.bytes.iter().all(u8::is_ascii_whitespace) {
+            return Poll::Ready(Ok(false));
+        }
+        match ready!(Pin::new(&mut *reader).poll_fill_buf(cx)) {
+            Ok(rest) => Poll::Ready(Ok(rest.is_empty())),
+            Err(why) => {
+                self.take();
+                Poll::Ready(Err(why))
+            }
+        }
+    }
+}
+
+/// An asynchronous Fasta Reader implementation.
+pub struct AsyncFastaReader<R> {
+    reader: R,
+    state: RecordState,
+}
+impl<R: AsyncBufRead + Unpin> AsyncFastaReader<R> {
+    /// # Example
+    /// Creates a new [`AsyncFastaReader`] from an object
+    /// which implements [`AsyncBufRead`].
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let fasta: &'static [u8] = b">sequence.id\nACGTACGT\n";
+    /// let mut reader = fxread::AsyncFastaReader::new(fasta);
+    /// let record = reader.next().await.unwrap().unwrap();
+    /// assert_eq!(record.id(), b"sequence.id");
+    /// # });
+    /// ```
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: RecordState::new(),
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncFastaReader<R> {
+    type Item = Result<Record>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let at_end = match ready!(this.state.poll_lines(&mut this.reader, cx, 2)) {
+            Ok(at_end) => at_end,
+            Err(why) => return Poll::Ready(Some(Err(anyhow!(why)))),
+        };
+        let (bytes, lines, num_lines) = this.state.take();
+        Poll::Ready(fasta_record(bytes, &lines[..num_lines], || at_end))
+    }
+}
+
+/// An asynchronous Fastq Reader implementation.
+pub struct AsyncFastqReader<R> {
+    reader: R,
+    state: RecordState,
+    strictness: Strictness,
+    /// The number of records parsed so far
+    count: usize,
+}
+impl<R: AsyncBufRead + Unpin> AsyncFastqReader<R> {
+    /// # Example
+    /// Creates a new [`AsyncFastqReader`] from an object
+    /// which implements [`AsyncBufRead`].
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let fastq: &'static [u8] = b"@sequence.id\nACGTACGT\n+\n$^$%^AAA\n";
+    /// let mut reader = fxread::AsyncFastqReader::new(fastq);
+    /// let record = reader.next().await.unwrap().unwrap();
+    /// assert_eq!(record.qual().unwrap(), b"$^$%^AAA");
+    /// # });
+    /// ```
+    pub fn new(reader: R) -> Self {
+        Self::with_strictness(reader, Strictness::default())
+    }
+
+    /// # Example
+    /// Creates a new [`AsyncFastqReader`] which validates each record
+    /// at the provided [`Strictness`].
+    ///
+    /// ```
+    /// use futures::StreamExt;
+    /// use fxread::{AsyncFastqReader, Strictness};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n123\n";
+    /// let mut reader = AsyncFastqReader::with_strictness(fastq, Strictness::Standard);
+    /// assert!(reader.next().await.unwrap().is_err());
+    /// # });
+    /// ```
+    pub fn with_strictness(reader: R, strictness: Strictness) -> Self {
+        Self {
+            reader,
+            state: RecordState::new(),
+            strictness,
+            count: 0,
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> Stream for AsyncFastqReader<R> {
+    type Item = Result<Record>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let at_end = match ready!(this.state.poll_lines(&mut this.reader, cx, 4)) {
+            Ok(at_end) => at_end,
+            Err(why) => return Poll::Ready(Some(Err(anyhow!(why)))),
+        };
+        let (bytes, lines, num_lines) = this.state.take();
+        if num_lines > 0 {
+            this.count += 1;
+        }
+        Poll::Ready(fastq_record(
+            bytes,
+            &lines[..num_lines],
+            this.strictness,
+            this.count,
+            || at_end,
+        ))
+    }
+}
+
+/// A boxed [`AsyncBufRead`] used by [`AsyncFastxReader`].
+pub type BoxedAsyncBufRead = Box<dyn AsyncBufRead + Unpin + Send>;
+
+/// An asynchronous reader for either format as determined by
+/// [`initialize_async_reader`].
+pub enum AsyncFastxReader {
+    Fasta(AsyncFastaReader<BoxedAsyncBufRead>),
+    Fastq(AsyncFastqReader<BoxedAsyncBufRead>),
+}
+
+impl Stream for AsyncFastxReader {
+    type Item = Result<Record>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            Self::Fasta(reader) => Pin::new(reader).poll_next(cx),
+            Self::Fastq(reader) => Pin::new(reader).poll_next(cx),
+        }
+    }
+}
+
+/// # Initializing an asynchronous reader
+/// Determines the compression (gzip, zstd, or none) from the magic
+/// bytes of the stream and the format from its first character.
+///
+/// ```
+/// use futures::StreamExt;
+/// use fxread::initialize_async_reader;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nACGT\n+\n1234\n";
+/// let reader = initialize_async_reader(fastq).await.unwrap();
+/// assert_eq!(reader.count().await, 2);
+/// # });
+/// ```
+pub async fn initialize_async_reader<R>(mut reader: R) -> Result<AsyncFastxReader>
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+{
+    let header = reader.fill_buf().await?;
+    let is_gzip = header.starts_with(&GZIP_MAGIC);
+    let is_zstd = header.starts_with(&ZSTD_MAGIC);
+    let mut buffer: BoxedAsyncBufRead = if is_gzip {
+        let mut decoder = GzipDecoder::new(reader);
+        decoder.multiple_members(true);
+        Box::new(BufReader::new(decoder))
+    } else if is_zstd {
+        let mut decoder = ZstdDecoder::new(reader);
+        decoder.multiple_members(true);
+        Box::new(BufReader::new(decoder))
+    } else {
+        Box::new(reader)
+    };
+    let first = buffer.fill_buf().await?;
+    if first.is_empty() {
+        return Err(anyhow!("No data in input stream"));
+    }
+    match first[0] {
+        b'>' => Ok(AsyncFastxReader::Fasta(AsyncFastaReader::new(buffer))),
+        b'@' => Ok(AsyncFastxReader::Fastq(AsyncFastqReader::new(buffer))),
+        _ => Err(anyhow!("Unrecognized file format")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{initialize_async_reader, AsyncFastaReader, AsyncFastqReader};
+    use crate::{FastqReader, FastxRead, Strictness};
+    use futures::StreamExt;
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::fs::File;
+    use tokio::io::{AsyncRead, BufReader, ReadBuf};
+
+    const SEQ_0: &[u8] =
+        b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA";
+
+    #[tokio::test]
+    async fn read_fasta_string() {
+        let fasta: &'static [u8] = b">seq.id\nACGT\n";
+        let mut reader = AsyncFastaReader::new(fasta);
+        let record = reader.next().await.unwrap().unwrap();
+        assert_eq!(record.id(), b"seq.id");
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(record.data(), b">seq.id\nACGT\n");
+        assert!(reader.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn read_fastq_string() {
+        let fastq: &'static [u8] = b"@seq.id\nACGT\n+\n7162\n";
+        let mut reader = AsyncFastqReader::new(fastq);
+        let record = reader.next().await.unwrap().unwrap();
+        assert_eq!(record.id(), b"seq.id");
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(record.qual().unwrap(), b"7162");
+        assert_eq!(record.data(), b"@seq.id\nACGT\n+\n7162\n");
+        assert!(reader.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn read_across_small_buffers() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n7162\n@seq.1\nTTTT\n+\n7162\n";
+        let buffer = BufReader::with_capacity(3, fastq);
+        let records: Vec<_> = AsyncFastqReader::new(buffer)
+            .map(|record| record.unwrap())
+            .collect()
+            .await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id(), b"seq.1");
+        assert_eq!(records[1].seq(), b"TTTT");
+    }
+
+    #[tokio::test]
+    async fn marker_in_quality() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n@@@@\n@seq.1\nTTTT\n+\n@F@F\n";
+        let buffer = BufReader::with_capacity(3, fastq);
+        let records: Vec<_> = AsyncFastqReader::new(buffer)
+            .map(|record| record.unwrap())
+            .collect()
+            .await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].qual().unwrap(), b"@@@@");
+        assert_eq!(records[1].qual().unwrap(), b"@F@F");
+    }
+
+    #[tokio::test]
+    async fn read_crlf() {
+        let fastq: &'static [u8] = b"@seq.0\r\nACGT\r\n+\r\n1234\r\n";
+        let mut reader = AsyncFastqReader::new(BufReader::with_capacity(3, fastq));
+        let record = reader.next().await.unwrap().unwrap();
+        assert!(record.valid());
+        assert_eq!(record.id(), b"seq.0");
+        assert_eq!(record.qual().unwrap(), b"1234");
+        assert_eq!(record.data(), b"@seq.0\nACGT\n+\n1234\n");
+    }
+
+    #[tokio::test]
+    async fn misplaced_marker() {
+        let fasta: &'static [u8] = b"seq.id\n>ACGT\n";
+        let mut reader = AsyncFastaReader::new(fasta);
+        assert!(reader.next().await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn truncated_record() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC\n";
+        let mut reader = AsyncFastqReader::with_strictness(fastq, Strictness::Standard);
+        assert!(reader.next().await.unwrap().is_ok());
+        let why = reader.next().await.unwrap().unwrap_err();
+        assert_eq!(
+            why.to_string(),
+            "Record 2: truncated record (expected 4 lines but found 2)"
+        );
+
+        // lenient readers end at a truncated record like the sync reader
+        let reader = AsyncFastqReader::new(BufReader::with_capacity(3, fastq));
+        assert_eq!(reader.count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn matches_sync_reader() {
+        let inputs: [&'static [u8]; 4] = [
+            b"@seq.0\nACGT\n-\n123\n@seq.1\nACGT\n+seq.2\n1234\n",
+            b"@seq.0\nACGT\n+\n1234\n\n\n",
+            b"@seq.0\nACGT\n+\n1234\n\n@seq.1\nA\n+\n1\n",
+            b"@seq.0\r\nACGT\r\n+\r\n1234",
+        ];
+        for input in inputs {
+            for strictness in [Strictness::Lenient, Strictness::Standard] {
+                let sync: Vec<_> = std::iter::from_fn({
+                    let mut reader = FastqReader::with_strictness(input, strictness);
+                    move || reader.next_record().transpose()
+                })
+                .map(|r| r.map(|r| r.data().to_vec()).map_err(|e| e.to_string()))
+                .take(3)
+                .collect();
+                let reader = AsyncFastqReader::with_strictness(input, strictness);
+                let stream: Vec<_> = reader
+                    .map(|r| r.map(|r| r.data().to_vec()).map_err(|e| e.to_string()))
+                    .take(3)
+                    .collect()
+                    .await;
+                assert_eq!(sync, stream);
+            }
+        }
+    }
+
+    /// Yields `data` and then fails every following read
+    struct FailingReader {
+        data: &'static [u8],
+    }
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.data.is_empty() {
+                return Poll::Ready(Err(io::Error::other("device failure")));
+            }
+            let size = self.data.len().min(buf.remaining());
+            buf.put_slice(&self.data[..size]);
+            self.data = &self.data[size..];
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn read_error_midway() {
+        let inputs: [&'static [u8]; 3] = [
+            b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC",
+            b"@seq.0\nACGT\n+\n1234\n",
+            b"@seq.0\nACGT\n+\n1234\n\n\n\n\n",
+        ];
+        for data in inputs {
+            let buffer = BufReader::with_capacity(4, FailingReader { data });
+            let mut reader = AsyncFastqReader::new(buffer);
+            assert_eq!(reader.next().await.unwrap().unwrap().id(), b"seq.0");
+            let why = reader.next().await.unwrap().unwrap_err();
+            assert_eq!(why.to_string(), "device failure");
+        }
+
+        let data = b">seq.0\nACGT\n\n";
+        let buffer = BufReader::with_capacity(4, FailingReader { data });
+        let mut reader = AsyncFastaReader::new(buffer);
+        assert!(reader.next().await.unwrap().is_ok());
+        let why = reader.next().await.unwrap().unwrap_err();
+        assert_eq!(why.to_string(), "device failure");
+    }
+
+    #[tokio::test]
+    async fn read_unterminated_line() {
+        let fasta: &'static [u8] = b">seq.0\nACGT\n>seq.1\nTTTT";
+        let records: Vec<_> = AsyncFastaReader::new(fasta)
+            .map(|record| record.unwrap())
+            .collect()
+            .await;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seq(), b"TTTT");
+        assert_eq!(records[1].data(), b">seq.1\nTTTT\n");
+    }
+
+    #[tokio::test]
+    async fn read_plaintext() {
+        let file = File::open("example/sequences.fq").await.unwrap();
+        let mut reader = initialize_async_reader(BufReader::new(file)).await.unwrap();
+        let record = reader.next().await.unwrap().unwrap();
+        assert_eq!(record.id(), b"seq.0");
+        assert_eq!(record.seq(), SEQ_0);
+        assert_eq!(reader.count().await, 9);
+    }
+
+    #[tokio::test]
+    async fn read_gzip() {
+        let file = File::open("example/sequences.fa.gz").await.unwrap();
+        let mut reader = initialize_async_reader(BufReader::new(file)).await.unwrap();
+        let record = reader.next().await.unwrap().unwrap();
+        assert_eq!(record.id(), b"seq.0");
+        assert_eq!(record.seq(), SEQ_0);
+        assert_eq!(reader.count().await, 9);
+    }
+
+    #[tokio::test]
+    async fn read_zstd() {
+        let file = File::open("example/sequences.fq.zst").await.unwrap();
+        let reader = initialize_async_reader(BufReader::new(file)).await.unwrap();
+        assert_eq!(reader.count().await, 10);
+    }
+
+    #[tokio::test]
+    async fn empty_stream() {
+        let empty: &'static [u8] = b"";
+        assert!(initialize_async_reader(empty).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unrecognized_format() {
+        let malformed: &'static [u8] = b"test\nACGT\n";
+        assert!(initialize_async_reader(malformed).await.is_err());
+    }
+}
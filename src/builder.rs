@@ -0,0 +1,544 @@
+use super::fastx::{EmptyReader, FastxRead, Format};
+use super::parse::skip_preamble;
+use super::{FastaReader, FastqReader, MmapFile, MmapReader, Record, Strictness};
+use anyhow::{anyhow, bail, Result};
+use niffler::send::compression;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read, Seek},
+    path::Path,
+};
+
+/// The default capacity of the buffer used by built readers
+const BUFFER_SIZE: usize = 4096 * 68;
+
+/// The compression of a fastx input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Detect the compression from the leading bytes of the input
+    #[default]
+    Auto,
+    /// Read the input as plaintext without checking for compression
+    None,
+    /// Gzip compression
+    Gzip,
+    /// Bzip2 compression
+    Bzip2,
+    /// Xz (lzma) compression
+    Xz,
+    /// Zstandard compression
+    Zstd,
+}
+impl Compression {
+    fn from_niffler(format: compression::Format) -> Self {
+        match format {
+            compression::Format::Gzip => Self::Gzip,
+            compression::Format::Bzip => Self::Bzip2,
+            compression::Format::Lzma => Self::Xz,
+            compression::Format::Zstd => Self::Zstd,
+            compression::Format::No => Self::None,
+        }
+    }
+}
+
+/// Checks whether the file is a regular file which can be memory mapped.
+///
+/// Unless `sniff` is false the file must also be uncompressed.
+fn is_mappable(file: &File, sniff: bool) -> Result<bool> {
+    if !file.metadata()?.is_file() {
+        return Ok(false);
+    }
+    if !sniff {
+        return Ok(true);
+    }
+    match niffler::send::sniff(Box::new(file)) {
+        Ok((_, format)) => Ok(format == compression::Format::No),
+        Err(niffler::Error::FileTooShort) => Ok(true),
+        Err(why) => Err(why.into()),
+    }
+}
+
+/// An opened input positioned at its first record.
+pub(crate) enum Input {
+    /// An uncompressed memory mapped file
+    Mapped(MmapFile),
+    /// A buffered input of a known format
+    Buffered(Format, BufReader<Box<dyn Read + Send>>),
+    /// An input without any records
+    Empty,
+}
+
+/// A builder for fasta and fastq readers with explicit settings.
+///
+/// Any setting which is not provided falls back to detection from the
+/// input: the compression is sniffed from the leading bytes and the
+/// format from the first character of the first record.
+///
+/// Built readers skip a leading UTF-8 byte order mark, blank lines,
+/// and comment lines (starting with '#' or ';') before the first record.
+///
+/// ```
+/// use fxread::{Compression, Format, ReaderBuilder};
+///
+/// let reader = ReaderBuilder::new()
+///     .format(Format::Fastq)
+///     .compression(Compression::Gzip)
+///     .buffer_size(1 << 16)
+///     .open("example/sequences.fq.gz")
+///     .unwrap();
+/// assert_eq!(reader.count(), 10);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ReaderBuilder {
+    format: Option<Format>,
+    compression: Compression,
+    buffer_size: usize,
+    allow_empty: bool,
+    strictness: Strictness,
+    mmap: bool,
+}
+impl Default for ReaderBuilder {
+    fn default() -> Self {
+        Self {
+            format: None,
+            compression: Compression::default(),
+            buffer_size: BUFFER_SIZE,
+            allow_empty: false,
+            strictness: Strictness::default(),
+            mmap: false,
+        }
+    }
+}
+impl ReaderBuilder {
+    /// Creates a new [`ReaderBuilder`] which detects all settings
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the input as the provided [`Format`] instead of detecting it
+    #[must_use]
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// # Usage
+    /// Sets the [`Compression`] of the input.
+    ///
+    /// A specific compression is validated against the input and
+    /// [`Compression::None`] reads the input as plaintext.
+    /// ```
+    /// use fxread::{Compression, ReaderBuilder};
+    ///
+    /// let builder = ReaderBuilder::new().compression(Compression::Zstd);
+    /// assert!(builder.open("example/sequences.fq.gz").is_err());
+    /// ```
+    #[must_use]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the capacity of the buffer used to read the input.
+    ///
+    /// Has no effect on files which are memory mapped (see [`ReaderBuilder::mmap`]).
+    ///
+    /// # Panics
+    /// Panics if `size` is zero
+    #[must_use]
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "buffer size must be greater than zero");
+        self.buffer_size = size;
+        self
+    }
+
+    /// # Usage
+    /// Builds an [`EmptyReader`] instead of returning an error for
+    /// inputs without any records (e.g. an empty file or one with only
+    /// comment lines). The format of such a reader is unknown.
+    /// ```
+    /// use fxread::{FastxRead, ReaderBuilder};
+    ///
+    /// let input: &'static [u8] = b"";
+    /// assert!(ReaderBuilder::new().build(input).is_err());
+    ///
+    /// let reader = ReaderBuilder::new().allow_empty(true).build(input).unwrap();
+    /// assert!(reader.format().is_none());
+    /// assert_eq!(reader.count(), 0);
+    /// ```
+    #[must_use]
+    pub fn allow_empty(mut self, allow_empty: bool) -> Self {
+        self.allow_empty = allow_empty;
+        self
+    }
+
+    /// # Usage
+    /// Sets the [`Strictness`] fastq records are validated at, which
+    /// also applies to [`count_records_with`](crate::count_records_with).
+    /// ```
+    /// use fxread::{ReaderBuilder, Strictness};
+    ///
+    /// let input: &'static [u8] = b"@seq.0\nACGT\n+\n123\n";
+    /// let mut reader = ReaderBuilder::new()
+    ///     .strictness(Strictness::Standard)
+    ///     .build(input)
+    ///     .unwrap();
+    /// assert!(reader.next_record().is_err());
+    /// ```
+    #[must_use]
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// # Usage
+    /// Memory maps uncompressed regular files opened with
+    /// [`ReaderBuilder::open`] (see [`MmapReader`]) instead of reading
+    /// them through a buffer. Other inputs are always buffered.
+    ///
+    /// Mapping is disabled by default.
+    /// ```
+    /// use fxread::ReaderBuilder;
+    ///
+    /// // SAFETY: the example file is not modified while it is mapped
+    /// let builder = unsafe { ReaderBuilder::new().mmap(true) };
+    /// let reader = builder.open("example/sequences.fq").unwrap();
+    /// assert_eq!(reader.count(), 10);
+    /// ```
+    ///
+    /// # Safety
+    /// Files opened by the builder must not be truncated or modified while
+    /// their readers are alive (see [`MmapFile`]).
+    #[must_use]
+    pub unsafe fn mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Returns the [`Strictness`] fastq records are validated at
+    pub(crate) fn strictness_level(&self) -> Strictness {
+        self.strictness
+    }
+
+    /// # Usage
+    /// Opens a reader over a file path.
+    ///
+    /// The file is read through a buffer unless memory mapping is
+    /// enabled with [`ReaderBuilder::mmap`].
+    /// ```
+    /// let reader = fxread::ReaderBuilder::new()
+    ///     .open("example/sequences.fa.bz2")
+    ///     .unwrap();
+    /// assert_eq!(reader.count(), 10);
+    /// ```
+    pub fn open<P>(&self, path: P) -> Result<Box<dyn FastxRead<Item = Record> + Send>>
+    where
+        P: AsRef<Path>,
+    {
+        self.reader(self.open_input(path)?)
+    }
+
+    /// # Usage
+    /// Builds a reader over any object which implements [`Read`]
+    /// (e.g. [`std::io::stdin`]).
+    /// ```
+    /// use fxread::ReaderBuilder;
+    ///
+    /// let input: &'static [u8] = b"\xEF\xBB\xBF# comment\n\n>seq.0\nACGT\n";
+    /// let mut reader = ReaderBuilder::new().build(input).unwrap();
+    /// assert_eq!(reader.next().unwrap().seq(), b"ACGT");
+    /// ```
+    pub fn build<R>(&self, reader: R) -> Result<Box<dyn FastxRead<Item = Record> + Send>>
+    where
+        R: Read + Send + 'static,
+    {
+        self.buffered(self.decompress(reader)?)
+    }
+
+    /// Builds a reader over an already decompressed input
+    pub(crate) fn buffered<R>(&self, reader: R) -> Result<Box<dyn FastxRead<Item = Record> + Send>>
+    where
+        R: Read + Send + 'static,
+    {
+        self.reader(self.buffered_input(Box::new(reader))?)
+    }
+
+    /// Opens a file path and positions it at its first record
+    pub(crate) fn open_input<P>(&self, path: P) -> Result<Input>
+    where
+        P: AsRef<Path>,
+    {
+        let mut file = File::open(path)?;
+        let sniff = match self.compression {
+            Compression::Auto => Some(true),
+            Compression::None => Some(false),
+            _ => None,
+        };
+        if let Some(sniff) = sniff.filter(|_| self.mmap) {
+            if is_mappable(&file, sniff)? {
+                // SAFETY: mapping was enabled through `ReaderBuilder::mmap`
+                // whose caller guarantees the file is not modified.
+                let mmap = unsafe { MmapFile::map(&file, self.format)? };
+                return Ok(match mmap {
+                    Some(mmap) => Input::Mapped(mmap),
+                    None => Input::Empty,
+                });
+            }
+            file.rewind()?;
+        }
+        self.buffered_input(self.decompress(file)?)
+    }
+
+    /// Buffers an already decompressed input and positions it at its
+    /// first record
+    pub(crate) fn buffered_input(&self, reader: Box<dyn Read + Send>) -> Result<Input> {
+        let mut buffer = BufReader::with_capacity(self.buffer_size, reader);
+        skip_preamble(&mut buffer)?;
+        let format = match (self.format, buffer.fill_buf()?.first()) {
+            (_, None) => return Ok(Input::Empty),
+            (Some(format), _) => format,
+            (None, Some(marker)) => {
+                Format::from_marker(*marker).ok_or(anyhow!("Unrecognized file format"))?
+            }
+        };
+        Ok(Input::Buffered(format, buffer))
+    }
+
+    /// Creates the reader over an opened input
+    fn reader(&self, input: Input) -> Result<Box<dyn FastxRead<Item = Record> + Send>> {
+        Ok(match input {
+            Input::Mapped(mmap) => Box::new(MmapReader::with_strictness(mmap, self.strictness)),
+            Input::Buffered(Format::Fasta, buffer) => Box::new(FastaReader::new(buffer)),
+            Input::Buffered(Format::Fastq, buffer) => {
+                Box::new(FastqReader::with_strictness(buffer, self.strictness))
+            }
+            Input::Empty => {
+                self.check_empty()?;
+                Box::new(EmptyReader)
+            }
+        })
+    }
+
+    /// Returns an error for inputs without any records unless allowed
+    pub(crate) fn check_empty(&self) -> Result<()> {
+        if self.allow_empty {
+            Ok(())
+        } else {
+            bail!("No data in input file")
+        }
+    }
+
+    /// Wraps the reader in the decoder of its compression
+    pub(crate) fn decompress<R>(&self, mut reader: R) -> Result<Box<dyn Read + Send>>
+    where
+        R: Read + Send + 'static,
+    {
+        if self.compression == Compression::None {
+            return Ok(Box::new(reader));
+        }
+
+        // inputs too short to hold a magic number are always plaintext
+        let mut magic = Vec::with_capacity(5);
+        (&mut reader).take(5).read_to_end(&mut magic)?;
+        let short = magic.len() < 5;
+        let stream = Cursor::new(magic).chain(reader);
+        if short {
+            return match self.compression {
+                Compression::Auto => Ok(Box::new(stream)),
+                expected => bail!("Expected {:?} compression but found None", expected),
+            };
+        }
+
+        let (reader, format) = niffler::send::get_reader(Box::new(stream))?;
+        let found = Compression::from_niffler(format);
+        match self.compression {
+            Compression::Auto => Ok(reader),
+            expected if expected == found => Ok(reader),
+            expected => bail!("Expected {:?} compression but found {:?}", expected, found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compression, ReaderBuilder};
+    use crate::test_utils::write_temp;
+    use crate::{FastxRead, Format, Strictness};
+    use std::io::Write;
+
+    #[test]
+    fn detect_compressions() {
+        for ext in ["fa", "fa.gz", "fa.bz2", "fa.xz", "fa.zst", "fq", "fq.gz"] {
+            let path = format!("example/sequences.{}", ext);
+            let reader = ReaderBuilder::new().open(path).unwrap();
+            assert_eq!(reader.count(), 10);
+        }
+    }
+
+    #[test]
+    fn explicit_compression() {
+        let cases = [
+            ("fq.gz", Compression::Gzip),
+            ("fq.bz2", Compression::Bzip2),
+            ("fq.xz", Compression::Xz),
+            ("fq.zst", Compression::Zstd),
+            ("fq", Compression::None),
+        ];
+        for (ext, compression) in cases {
+            let path = format!("example/sequences.{}", ext);
+            let reader = ReaderBuilder::new()
+                .compression(compression)
+                .open(path)
+                .unwrap();
+            assert_eq!(reader.count(), 10);
+        }
+    }
+
+    #[test]
+    fn mismatched_compression() {
+        let builder = ReaderBuilder::new().compression(Compression::Gzip);
+        let err = builder.open("example/sequences.fq.xz").err().unwrap();
+        assert_eq!(err.to_string(), "Expected Gzip compression but found Xz");
+        assert!(builder.open("example/sequences.fq").is_err());
+    }
+
+    #[test]
+    fn no_compression_reads_raw() {
+        let builder = ReaderBuilder::new().compression(Compression::None);
+        assert!(builder.open("example/sequences.fq.gz").is_err());
+    }
+
+    #[test]
+    fn forced_format() {
+        let input: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n";
+        let mut reader = ReaderBuilder::new()
+            .format(Format::Fasta)
+            .build(input)
+            .unwrap();
+        assert!(reader.next_record().is_err());
+
+        let mut reader = ReaderBuilder::new()
+            .format(Format::Fastq)
+            .build(input)
+            .unwrap();
+        assert_eq!(reader.next().unwrap().qual().unwrap(), b"1234");
+    }
+
+    /// Builds a [`ReaderBuilder`] which memory maps files
+    fn mapped() -> ReaderBuilder {
+        // SAFETY: test files are not modified while they are mapped
+        unsafe { ReaderBuilder::new().mmap(true) }
+    }
+
+    #[test]
+    fn forced_format_mmap() {
+        let path = write_temp("forced.fq", b"@seq.0\nACGT\n+\n1234\n");
+        let reader = mapped().format(Format::Fasta).open(&path);
+        let mut reader = reader.unwrap();
+        assert!(reader.next_record().is_err());
+        let reader = mapped().format(Format::Fastq).open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.unwrap().count(), 1);
+    }
+
+    #[test]
+    fn skip_preamble_compressed() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder
+            .write_all(b"\xEF\xBB\xBF;comment\n\n>seq.0\nACGT\n>seq.1\nTT\n")
+            .unwrap();
+        let data = encoder.finish().unwrap();
+        let reader = ReaderBuilder::new().build(std::io::Cursor::new(data));
+        assert_eq!(reader.unwrap().count(), 2);
+    }
+
+    #[test]
+    fn skip_preamble_mmap() {
+        let path = write_temp("preamble.fq", b"\n\n# run 1\n@seq.0\nACGT\n+\n1234\n");
+        let reader = mapped().open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.unwrap().count(), 1);
+    }
+
+    #[test]
+    fn mmap_strictness() {
+        let path = write_temp("strict.fq", b"@seq.0\nACGT\n+\n123\n");
+        for builder in [ReaderBuilder::new(), mapped()] {
+            let mut reader = builder
+                .strictness(Strictness::Standard)
+                .open(&path)
+                .unwrap();
+            assert!(reader.next_record().is_err());
+            assert_eq!(builder.open(&path).unwrap().count(), 1);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn short_inputs() {
+        let input: &'static [u8] = b">a\nA";
+        let reader = ReaderBuilder::new().build(input).unwrap();
+        assert_eq!(reader.count(), 1);
+
+        let input: &'static [u8] = b">a\nA";
+        let builder = ReaderBuilder::new().compression(Compression::Gzip);
+        assert!(builder.build(input).is_err());
+    }
+
+    #[test]
+    fn allow_empty_inputs() {
+        let builder = ReaderBuilder::new().allow_empty(true);
+        for input in [&b""[..], b"\n\n", b"# comment\n"] {
+            let reader = builder.build(input).unwrap();
+            assert!(reader.format().is_none());
+            assert_eq!(reader.count(), 0);
+        }
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(b"").unwrap();
+        let data = encoder.finish().unwrap();
+        let reader = builder.build(std::io::Cursor::new(data)).unwrap();
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[test]
+    fn allow_empty_files() {
+        for builder in [ReaderBuilder::new(), mapped()] {
+            let path = write_temp("empty.fq", b"");
+            assert!(builder.open(&path).is_err());
+            let reader = builder.allow_empty(true).open(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert!(reader.format().is_none());
+            assert_eq!(reader.count(), 0);
+
+            let path = write_temp("blank.fq", b"\n\n");
+            let builder = builder.allow_empty(true).format(Format::Fastq);
+            let reader = builder.open(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert!(reader.format().is_none());
+            assert_eq!(reader.count(), 0);
+        }
+    }
+
+    #[test]
+    fn allow_empty_with_records() {
+        let input: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n";
+        let reader = ReaderBuilder::new().allow_empty(true).build(input).unwrap();
+        assert_eq!(reader.format(), Some(Format::Fastq));
+        assert_eq!(reader.count(), 1);
+
+        let reader = ReaderBuilder::new().open("example/sequences.fa").unwrap();
+        assert_eq!(reader.format(), Some(Format::Fasta));
+    }
+
+    #[test]
+    fn empty_inputs() {
+        let input: &'static [u8] = b"\n# nothing here\n";
+        let err = ReaderBuilder::new().build(input).err().unwrap();
+        assert_eq!(err.to_string(), "No data in input file");
+
+        let input: &'static [u8] = b"seq.0\nACGT\n";
+        assert!(ReaderBuilder::new().build(input).is_err());
+    }
+}
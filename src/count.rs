@@ -0,0 +1,364 @@
+use super::builder::Input;
+use super::fastq::Strictness;
+use super::fastx::Format;
+use super::parse::{check_fasta, check_fastq, line_contents, locate_lines, read_lines};
+use super::ReaderBuilder;
+use anyhow::Result;
+use std::{
+    io::{self, BufRead, Read},
+    path::Path,
+};
+
+/// The number of records and bases of an input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    records: u64,
+    bases: u64,
+    format: Option<Format>,
+}
+impl Counts {
+    /// Returns the number of records
+    #[must_use]
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the total sequence length of all records
+    #[must_use]
+    pub fn bases(&self) -> u64 {
+        self.bases
+    }
+
+    /// Returns the [`Format`] of the input or `None` if it is empty
+    #[must_use]
+    pub fn format(&self) -> Option<Format> {
+        self.format
+    }
+}
+
+/// Counts the records and bases of an input positioned at its first
+/// record where each record spans `num_lines` lines accepted by `check`.
+///
+/// Records are located with a vectorized newline search directly over
+/// the buffered data (or the mapped file) and checked on their borrowed
+/// lines. Only a record which reaches the end of the buffered data is
+/// copied so its lines can be read across refills.
+fn count_lines<B, F>(
+    reader: &mut B,
+    format: Format,
+    num_lines: usize,
+    mut check: F,
+) -> Result<Counts>
+where
+    B: BufRead,
+    F: FnMut(&[&[u8]], usize, &mut dyn FnMut() -> bool) -> Result<bool>,
+{
+    let mut counts = Counts {
+        format: Some(format),
+        ..Counts::default()
+    };
+    let mut ranges = [0..0, 0..0, 0..0, 0..0];
+    let mut bytes = Vec::with_capacity(300);
+    let mut sizes = [0; 4];
+    loop {
+        let number = counts.records as usize + 1;
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            Err(why) => return Err(why.into()),
+        };
+        let (found, used) = locate_lines(available, &mut ranges[..num_lines]);
+        // a record followed by more buffered data is complete and not at the end
+        let (valid, bases) = if found == num_lines && used < available.len() {
+            let contents = ranges.clone().map(|range| &available[range]);
+            let valid = check(&contents[..found], number, &mut || false)?;
+            let bases = contents[1].len();
+            reader.consume(used);
+            (valid, bases)
+        } else {
+            bytes.clear();
+            let found = read_lines(reader, &mut bytes, &mut sizes[..num_lines])?;
+            let contents = line_contents(&bytes, &sizes[..found]);
+            let mut at_end = || matches!(reader.fill_buf(), Ok(rest) if rest.is_empty());
+            let valid = check(&contents[..found], number, &mut at_end)?;
+            (valid, contents[1].len())
+        };
+        if !valid {
+            return Ok(counts);
+        }
+        counts.records += 1;
+        counts.bases += bases as u64;
+    }
+}
+
+/// Counts the records and bases of a fastq input positioned at its
+/// first record.
+///
+/// Records are checked with the same rules as [`FastqReader`](crate::FastqReader)
+/// at the given [`Strictness`], so a lenient count ends at a truncated
+/// final record where a stricter one returns an error.
+fn count_fastq<B: BufRead>(reader: &mut B, strictness: Strictness) -> Result<Counts> {
+    count_lines(reader, Format::Fastq, 4, |lines, number, at_end| {
+        check_fastq(lines, strictness, number, at_end)
+    })
+}
+
+/// Counts the records and bases of a fasta input positioned at its
+/// first record.
+///
+/// Records are checked with the same rules as [`FastaReader`](crate::FastaReader):
+/// each is a header line followed by a single sequence line.
+fn count_fasta<B: BufRead>(reader: &mut B) -> Result<Counts> {
+    count_lines(reader, Format::Fasta, 2, |lines, _, at_end| {
+        check_fasta(lines, at_end)
+    })
+}
+
+/// Counts the records and bases of an opened input
+fn count_input(input: Input, builder: &ReaderBuilder) -> Result<Counts> {
+    match input {
+        Input::Mapped(mmap) => {
+            let mut data = mmap.record_data();
+            match mmap.format() {
+                Format::Fasta => count_fasta(&mut data),
+                Format::Fastq => count_fastq(&mut data, builder.strictness_level()),
+            }
+        }
+        Input::Buffered(Format::Fasta, mut buffer) => count_fasta(&mut buffer),
+        Input::Buffered(Format::Fastq, mut buffer) => {
+            count_fastq(&mut buffer, builder.strictness_level())
+        }
+        Input::Empty => {
+            builder.check_empty()?;
+            Ok(Counts::default())
+        }
+    }
+}
+
+/// # Usage
+/// Counts the records and bases of a file without creating [`Record`](crate::Record)s.
+///
+/// Accepts any input supported by [`initialize_reader`](crate::initialize_reader).
+/// ```
+/// let counts = fxread::count_records("example/sequences.fq.gz").unwrap();
+/// assert_eq!(counts.records(), 10);
+/// assert_eq!(counts.format(), Some(fxread::Format::Fastq));
+/// ```
+pub fn count_records<P: AsRef<Path>>(path: P) -> Result<Counts> {
+    count_records_with(path, ReaderBuilder::new())
+}
+
+/// # Usage
+/// Counts the records and bases of a file opened with the provided
+/// [`ReaderBuilder`].
+/// ```
+/// use fxread::{count_records_with, ReaderBuilder};
+///
+/// let builder = ReaderBuilder::new().allow_empty(true);
+/// let counts = count_records_with("example/sequences.fa", builder).unwrap();
+/// assert_eq!(counts.records(), 10);
+/// ```
+pub fn count_records_with<P: AsRef<Path>>(path: P, builder: ReaderBuilder) -> Result<Counts> {
+    count_input(builder.open_input(path)?, &builder)
+}
+
+/// # Usage
+/// Counts the records and bases of any object which implements [`Read`]
+/// (e.g. [`std::io::stdin`]).
+/// ```
+/// use fxread::{count_reader, ReaderBuilder};
+///
+/// let input: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC\n+\n12\n";
+/// let counts = count_reader(input, ReaderBuilder::new()).unwrap();
+/// assert_eq!(counts.records(), 2);
+/// assert_eq!(counts.bases(), 6);
+/// ```
+pub fn count_reader<R>(reader: R, builder: ReaderBuilder) -> Result<Counts>
+where
+    R: Read + Send + 'static,
+{
+    let input = builder.buffered_input(builder.decompress(reader)?)?;
+    count_input(input, &builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_reader, count_records, count_records_with};
+    use crate::{initialize_reader, FastxRead, Format, ReaderBuilder, Strictness};
+    use std::io::BufReader;
+
+    fn count(input: &'static [u8]) -> anyhow::Result<(u64, u64)> {
+        let counts = count_reader(input, ReaderBuilder::new())?;
+        Ok((counts.records(), counts.bases()))
+    }
+
+    #[test]
+    fn matches_reader() {
+        for ext in [
+            "fa", "fa.gz", "fa.bz2", "fa.xz", "fa.zst", "fq", "fq.gz", "fq.bz2", "fq.xz", "fq.zst",
+        ] {
+            let path = format!("example/sequences.{}", ext);
+            let counts = count_records(&path).unwrap();
+            assert_eq!(
+                counts.records(),
+                initialize_reader(&path).unwrap().count() as u64
+            );
+            let bases: usize = initialize_reader(&path)
+                .unwrap()
+                .map(|r| r.seq().len())
+                .sum();
+            assert_eq!(counts.bases(), bases as u64);
+
+            let builder = ReaderBuilder::new().strictness(Strictness::Strict);
+            let counts = count_records_with(&path, builder).unwrap();
+            assert_eq!(
+                counts.records(),
+                builder.open(&path).unwrap().count() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn small_buffers() {
+        let input: &'static [u8] =
+            b"@seq.0\r\nACGT\r\n+\r\n1234\r\n@seq.1\r\nACGTA\r\n+\r\n12345\r\n";
+        let builder = ReaderBuilder::new();
+        let buffered = BufReader::with_capacity(3, input);
+        let counts = count_reader(buffered, builder.buffer_size(2)).unwrap();
+        assert_eq!((counts.records(), counts.bases()), (2, 9));
+    }
+
+    #[test]
+    fn all_buffer_boundaries() {
+        let inputs: [(&'static [u8], (u64, u64)); 3] = [
+            (
+                b"@seq.0\r\nACGT\r\n+\r\n1234\r\n@seq.1\nACGTA\n+seq.1\n12345\n@seq.2\nA\n+\n1",
+                (3, 10),
+            ),
+            (b">seq.0\nACGT\n>seq.1\r\nACGTA\r\n>seq.2\nA", (3, 10)),
+            (b"@seq.0\nACGT\n+\n1234\n\n\n", (1, 4)),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(count(input).unwrap(), expected);
+            for capacity in 1..=input.len() {
+                let buffered = BufReader::with_capacity(capacity, input);
+                let counts = count_reader(buffered, ReaderBuilder::new().buffer_size(capacity));
+                let counts = counts.unwrap();
+                assert_eq!((counts.records(), counts.bases()), expected);
+            }
+        }
+        assert_eq!(
+            count(b"@seq.0\nACGT\n+\n1234\n\n@seq.1\nA\n+\n1\n").ok(),
+            None
+        );
+    }
+
+    #[test]
+    fn quality_starting_with_marker() {
+        assert_eq!(
+            count(b"@seq.0\nACGT\n+\n@@@@\n@seq.1\nA\n+\n@\n").unwrap(),
+            (2, 5)
+        );
+    }
+
+    #[test]
+    fn fastq_structure() {
+        let strict = |input: &'static [u8]| {
+            let builder = ReaderBuilder::new().strictness(Strictness::Standard);
+            count_reader(input, builder).map(|c| (c.records(), c.bases()))
+        };
+        let err = strict(b"@seq.0\nACGT\n-\n1234\n").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Record 1: expected '+' at the start of the third line but found '-'"
+        );
+        let err = strict(b"@seq.0\nACGT\n+\n1234\n@seq.1\nACGT\n+\n123\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Record 2: sequence length (4) does not match quality length (3)"
+        );
+        assert!(strict(b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC").is_err());
+        assert!(count(b"@seq.0\nACGT\n+\n1234\nseq.1\nACGT\n+\n1234\n").is_err());
+
+        // lenient counts only split records on their line layout
+        assert_eq!(count(b"@seq.0\nACGT\n-\n123\n").unwrap(), (1, 4));
+    }
+
+    #[test]
+    fn fastq_trailing_content() {
+        assert_eq!(count(b"@seq.0\nACGT\n+\n1234\n\n\n").unwrap(), (1, 4));
+        assert_eq!(count(b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC").unwrap(), (1, 4));
+        assert_eq!(count(b"@seq.0\nACGT\n+\n1234").unwrap(), (1, 4));
+        assert!(count(b"@seq.0\nACGT\n+\n1234\n\n@seq.1\nA\n+\n1\n").is_err());
+    }
+
+    #[test]
+    fn fasta_structure() {
+        assert!(count(b">seq.0\nACGT\nAC\n>seq.1\nA\n").is_err());
+        assert_eq!(count(b">seq.0\r\nACGT\r\n>seq.1\r\n").unwrap(), (1, 4));
+        assert_eq!(count(b"# comment\n>seq.0\nACGT").unwrap(), (1, 4));
+    }
+
+    #[test]
+    fn agrees_with_reader() {
+        let inputs: [&'static [u8]; 7] = [
+            b">seq.0\nACGT\nAC\n>seq.1\nA\n",
+            b">seq.0\nACGT\n>seq.1\n",
+            b">seq.0\nACGT\n\n\n",
+            b"@seq.0\nACGT\n-\n123\n@seq.1\nAC\n+\n12\n",
+            b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC\n",
+            b"@seq.0\nACGT\n+seq.1\n1234\n",
+            b"@seq.0\nACGT\n+\n1234\n\n@seq.1\nA\n+\n1\n",
+        ];
+        for input in inputs {
+            for strictness in [Strictness::Lenient, Strictness::Standard] {
+                let builder = ReaderBuilder::new().strictness(strictness);
+                let counted = count_reader(input, builder).map(|c| c.records());
+                let mut reader = builder.build(input).unwrap();
+                let mut read = 0;
+                let parsed = loop {
+                    match reader.next_record() {
+                        Ok(Some(_)) => read += 1,
+                        Ok(None) => break Ok(read),
+                        Err(why) => break Err(why),
+                    }
+                };
+                assert_eq!(counted.ok(), parsed.ok(), "{:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn forced_format() {
+        let builder = ReaderBuilder::new().format(Format::Fasta);
+        let input: &'static [u8] = b"ACGT\n>seq.0\nACGT\n";
+        assert!(count_reader(input, builder).is_err());
+    }
+
+    #[test]
+    fn empty_input() {
+        let input: &'static [u8] = b"";
+        assert!(count_reader(input, ReaderBuilder::new()).is_err());
+        let builder = ReaderBuilder::new().allow_empty(true);
+        let counts = count_reader(input, builder).unwrap();
+        assert_eq!(counts.records(), 0);
+        assert!(counts.format().is_none());
+    }
+
+    #[test]
+    fn count_mapped_with_builder() {
+        // SAFETY: the example file is not modified while it is mapped
+        let builder = unsafe { ReaderBuilder::new().mmap(true) }.format(Format::Fastq);
+        let counts = count_records_with("example/sequences.fq", builder).unwrap();
+        assert_eq!(counts.records(), 10);
+        assert_eq!(counts.format(), Some(Format::Fastq));
+
+        let builder = builder.strictness(Strictness::Strict);
+        assert_eq!(
+            count_records_with("example/sequences.fq", builder).unwrap(),
+            counts
+        );
+    }
+}
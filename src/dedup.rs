@@ -0,0 +1,356 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use super::sample::next_pair;
+use super::search::header_id;
+use anyhow::Result;
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::Hasher,
+};
+
+/// The part of a record which identifies duplicates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupKey {
+    /// Records with the same sequence (case-insensitive) are duplicates
+    #[default]
+    Sequence,
+    /// Records with the same ID (the header up to the first whitespace)
+    /// are duplicates
+    Id,
+    /// Records with both the same ID and sequence are duplicates
+    Both,
+}
+
+/// The keys which have already been seen
+enum Seen {
+    Full(HashSet<Vec<u8>>),
+    Hashed(HashSet<u64>),
+}
+
+/// The shared state of the deduplicating adaptors
+struct Deduplicator {
+    key: DedupKey,
+    canonical: bool,
+    seen: Seen,
+    unique: u64,
+    duplicates: u64,
+}
+impl Deduplicator {
+    fn new() -> Self {
+        Self {
+            key: DedupKey::default(),
+            canonical: false,
+            seen: Seen::Full(HashSet::new()),
+            unique: 0,
+            duplicates: 0,
+        }
+    }
+
+    /// Returns the key of a record or of a pair of mates.
+    ///
+    /// A canonical key is the smaller of the sequences as read and their
+    /// reverse complement. For pairs that is the mates read from the
+    /// other strand, i.e. `(rc(R2), rc(R1))` for the pair `(R1, R2)`.
+    fn key(&self, records: &[&Record]) -> Vec<u8> {
+        let mut key = Vec::new();
+        if matches!(self.key, DedupKey::Id | DedupKey::Both) {
+            for record in records {
+                key.extend_from_slice(header_id(record.id()));
+                key.push(b'\n');
+            }
+        }
+        if matches!(self.key, DedupKey::Sequence | DedupKey::Both) {
+            let mut forward = Vec::new();
+            for record in records {
+                forward.extend_from_slice(&record.seq_upper());
+                forward.push(b'\n');
+            }
+            if self.canonical {
+                let mut reverse = Vec::with_capacity(forward.len());
+                for record in records.iter().rev() {
+                    reverse.extend_from_slice(&record.seq_rev_comp());
+                    reverse.push(b'\n');
+                }
+                reverse.make_ascii_uppercase();
+                key.extend_from_slice(forward.min(reverse).as_slice());
+            } else {
+                key.extend_from_slice(&forward);
+            }
+        }
+        key
+    }
+
+    /// Records the key of the records and returns whether it is new
+    fn insert(&mut self, records: &[&Record]) -> bool {
+        let key = self.key(records);
+        let new = match &mut self.seen {
+            Seen::Full(seen) => seen.insert(key),
+            Seen::Hashed(seen) => {
+                let mut hasher = DefaultHasher::new();
+                hasher.write(&key);
+                seen.insert(hasher.finish())
+            }
+        };
+        if new {
+            self.unique += 1;
+        } else {
+            self.duplicates += 1;
+        }
+        new
+    }
+
+    fn hashed(&mut self, hashed: bool) {
+        self.seen = if hashed {
+            Seen::Hashed(HashSet::new())
+        } else {
+            Seen::Full(HashSet::new())
+        };
+    }
+}
+
+/// Implements the shared setters and accessors of the deduplicating adaptors
+macro_rules! dedup_settings {
+    () => {
+        /// Sets the part of each record which identifies duplicates
+        #[must_use]
+        pub fn key(mut self, key: DedupKey) -> Self {
+            self.dedup.key = key;
+            self
+        }
+
+        /// Sets whether a sequence and its reverse complement are
+        /// considered identical. A pair `(R1, R2)` is identical to the
+        /// pair read from the other strand, `(rc(R2), rc(R1))`.
+        #[must_use]
+        pub fn canonical(mut self, canonical: bool) -> Self {
+            self.dedup.canonical = canonical;
+            self
+        }
+
+        /// Sets whether only a 64-bit hash of each key is kept.
+        ///
+        /// This bounds memory to a few bytes per unique record at the
+        /// cost of a very small chance of a hash collision dropping a
+        /// unique record.
+        #[must_use]
+        pub fn hashed(mut self, hashed: bool) -> Self {
+            self.dedup.hashed(hashed);
+            self
+        }
+
+        /// Returns the number of unique records (or pairs) yielded
+        #[must_use]
+        pub fn unique(&self) -> u64 {
+            self.dedup.unique
+        }
+
+        /// Returns the number of duplicate records (or pairs) removed
+        #[must_use]
+        pub fn duplicates(&self) -> u64 {
+            self.dedup.duplicates
+        }
+    };
+}
+
+/// A reader adaptor which removes duplicate records keeping the first
+/// occurrence of each.
+///
+/// ```
+/// use fxread::{initialize_reader, DedupKey, DedupReader};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let mut dedup = DedupReader::new(reader)
+///     .key(DedupKey::Sequence)
+///     .canonical(true)
+///     .hashed(true);
+/// assert_eq!(dedup.by_ref().count(), 10);
+/// assert_eq!(dedup.duplicates(), 0);
+/// ```
+pub struct DedupReader<R: FastxRead> {
+    reader: R,
+    dedup: Deduplicator,
+}
+impl<R: FastxRead> DedupReader<R> {
+    /// Creates a new [`DedupReader`] removing records with identical
+    /// sequences
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            dedup: Deduplicator::new(),
+        }
+    }
+
+    dedup_settings!();
+}
+
+impl<R: FastxRead> FastxRead for DedupReader<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        while let Some(record) = self.reader.next_record()? {
+            if self.dedup.insert(&[&record]) {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for DedupReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+/// A reader adaptor which removes duplicate pairs of records from
+/// paired inputs.
+///
+/// A pair is a duplicate when the keys of both mates match those of an
+/// earlier pair.
+///
+/// ```
+/// use fxread::{initialize_reader, PairedDedupReader};
+///
+/// let r1 = initialize_reader("example/sequences.fq").unwrap();
+/// let r2 = initialize_reader("example/sequences.fq.gz").unwrap();
+/// let mut dedup = PairedDedupReader::new(r1, r2);
+/// assert_eq!(dedup.by_ref().count(), 10);
+/// assert_eq!(dedup.unique(), 10);
+/// ```
+pub struct PairedDedupReader<R1: FastxRead, R2: FastxRead> {
+    r1: R1,
+    r2: R2,
+    dedup: Deduplicator,
+}
+impl<R1: FastxRead, R2: FastxRead> PairedDedupReader<R1, R2> {
+    /// Creates a new [`PairedDedupReader`] removing pairs with identical
+    /// sequences
+    pub fn new(r1: R1, r2: R2) -> Self {
+        Self {
+            r1,
+            r2,
+            dedup: Deduplicator::new(),
+        }
+    }
+
+    dedup_settings!();
+
+    /// Returns the next unique pair of records.
+    ///
+    /// Returns an error if the inputs have different numbers of records.
+    pub fn next_pair(&mut self) -> Result<Option<(Record, Record)>> {
+        while let Some((a, b)) = next_pair(&mut self.r1, &mut self.r2)? {
+            if self.dedup.insert(&[&a, &b]) {
+                return Ok(Some((a, b)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R1: FastxRead, R2: FastxRead> Iterator for PairedDedupReader<R1, R2> {
+    type Item = (Record, Record);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_pair() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupKey, DedupReader, PairedDedupReader};
+    use crate::{FastaReader, Record};
+
+    const FASTA: &[u8] = b">a desc\nACGT\n>b\nacgt\n>a other\nTTGA\n>c\nTCAA\n>b\nACGT\n";
+
+    fn ids<I: Iterator<Item = Record>>(records: I) -> Vec<String> {
+        records.map(|r| r.id_str().to_string()).collect()
+    }
+
+    #[test]
+    fn by_sequence() {
+        for hashed in [false, true] {
+            let mut dedup = DedupReader::new(FastaReader::new(FASTA)).hashed(hashed);
+            assert_eq!(ids(dedup.by_ref()), ["a desc", "a other", "c"]);
+            assert_eq!((dedup.unique(), dedup.duplicates()), (3, 2));
+        }
+    }
+
+    #[test]
+    fn canonical_sequence() {
+        let dedup = DedupReader::new(FastaReader::new(FASTA)).canonical(true);
+        assert_eq!(ids(dedup), ["a desc", "a other"]);
+    }
+
+    #[test]
+    fn by_id() {
+        let dedup = DedupReader::new(FastaReader::new(FASTA)).key(DedupKey::Id);
+        assert_eq!(ids(dedup), ["a desc", "b", "c"]);
+    }
+
+    #[test]
+    fn by_both() {
+        let mut dedup = DedupReader::new(FastaReader::new(FASTA))
+            .key(DedupKey::Both)
+            .hashed(true);
+        assert_eq!(ids(dedup.by_ref()), ["a desc", "b", "a other", "c"]);
+        assert_eq!(dedup.duplicates(), 1);
+    }
+
+    #[test]
+    fn paired() {
+        let r1: &'static [u8] = b">p0\nAAAA\n>p1\nAAAA\n>p2\nAAAA\n";
+        let r2: &'static [u8] = b">p0\nCCCC\n>p1\nGGGG\n>p2\nCCCC\n";
+        let mut dedup = PairedDedupReader::new(FastaReader::new(r1), FastaReader::new(r2));
+        let pairs: Vec<_> = dedup
+            .by_ref()
+            .map(|(a, _)| a.id_str().to_string())
+            .collect();
+        assert_eq!(pairs, ["p0", "p1"]);
+        assert_eq!(dedup.duplicates(), 1);
+
+        // the key of each mate is kept separate
+        let r1: &'static [u8] = b">p0\nAA\n>p1\nA\n";
+        let r2: &'static [u8] = b">p0\nC\n>p1\nAC\n";
+        let dedup = PairedDedupReader::new(FastaReader::new(r1), FastaReader::new(r2));
+        assert_eq!(dedup.count(), 2);
+    }
+
+    #[test]
+    fn paired_canonical() {
+        // p1 is p0 read from the other strand and p2 only reverses its second mate
+        let r1: &'static [u8] = b">p0\nAACG\n>p1\nTACC\n>p2\nAACG\n";
+        let r2: &'static [u8] = b">p0\nGGTA\n>p1\ncgtt\n>p2\nTACC\n";
+        let dedup = PairedDedupReader::new(FastaReader::new(r1), FastaReader::new(r2));
+        assert_eq!(dedup.count(), 3);
+
+        let mut dedup = PairedDedupReader::new(FastaReader::new(r1), FastaReader::new(r2))
+            .canonical(true)
+            .hashed(true);
+        let pairs: Vec<_> = dedup
+            .by_ref()
+            .map(|(a, _)| a.id_str().to_string())
+            .collect();
+        assert_eq!(pairs, ["p0", "p2"]);
+        assert_eq!(dedup.duplicates(), 1);
+    }
+
+    #[test]
+    fn paired_length_mismatch() {
+        let r1: &'static [u8] = b">p0\nAAAA\n>p1\nAAAA\n";
+        let r2: &'static [u8] = b">p0\nCCCC\n";
+        let mut dedup = PairedDedupReader::new(FastaReader::new(r1), FastaReader::new(r2));
+        assert!(dedup.next_pair().unwrap().is_some());
+        assert!(dedup.next_pair().is_err());
+    }
+}
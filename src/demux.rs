@@ -0,0 +1,710 @@
+use super::fastx::FastxRead;
+use super::record::Record;
+use super::sample::next_pair;
+use super::split::{create_writer, finish, PartWriter};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The name of the output of reads which match no sample
+const UNDETERMINED: &str = "undetermined";
+
+/// A sample of a [`BarcodeSheet`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sample {
+    name: String,
+    i7: Vec<u8>,
+    i5: Option<Vec<u8>>,
+}
+
+/// The barcodes of the samples of a pooled run.
+///
+/// Every sample has an i7 barcode and either all or none of them have
+/// an i5 barcode (dual indexing).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BarcodeSheet {
+    samples: Vec<Sample>,
+}
+impl BarcodeSheet {
+    /// Creates an empty [`BarcodeSheet`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks and adds a sample
+    fn push(&mut self, name: &str, i7: &[u8], i5: Option<&[u8]>) -> Result<()> {
+        if name == UNDETERMINED {
+            bail!("Sample name '{}' is reserved", UNDETERMINED);
+        }
+        if self.samples.iter().any(|s| s.name == name) {
+            bail!("Duplicate sample name in barcode sheet: {}", name);
+        }
+        for barcode in std::iter::once(i7).chain(i5) {
+            if barcode.is_empty() || !barcode.iter().all(u8::is_ascii_alphabetic) {
+                bail!(
+                    "Invalid barcode for sample {}: '{}'",
+                    name,
+                    String::from_utf8_lossy(barcode)
+                );
+            }
+        }
+        if let Some(first) = self.samples.first() {
+            if first.i5.is_some() != i5.is_some() {
+                bail!("Barcode sheet mixes single and dual indexed samples");
+            }
+        }
+        self.samples.push(Sample {
+            name: name.to_string(),
+            i7: i7.to_ascii_uppercase(),
+            i5: i5.map(<[u8]>::to_ascii_uppercase),
+        });
+        Ok(())
+    }
+
+    /// Adds a single indexed sample
+    pub fn add(&mut self, name: &str, barcode: &[u8]) -> Result<()> {
+        self.push(name, barcode, None)
+    }
+
+    /// Adds a dual indexed sample
+    pub fn add_dual(&mut self, name: &str, i7: &[u8], i5: &[u8]) -> Result<()> {
+        self.push(name, i7, Some(i5))
+    }
+
+    /// # Usage
+    /// Reads a barcode sheet of one sample per line with its name, i7
+    /// barcode and optional i5 barcode separated by tabs, commas or
+    /// spaces. Blank lines and lines starting with '#' are skipped.
+    /// ```
+    /// use fxread::BarcodeSheet;
+    ///
+    /// let path = std::env::temp_dir().join("fxread-doc-barcodes.tsv");
+    /// std::fs::write(&path, "# sample\ti7\nS1\tACGTAC\nS2\tTTGGCA\n").unwrap();
+    /// let sheet = BarcodeSheet::from_file(&path).unwrap();
+    /// assert_eq!(sheet.names().collect::<Vec<_>>(), ["S1", "S2"]);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let mut sheet = Self::new();
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<_> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|f| !f.is_empty())
+                .collect();
+            let result = match fields[..] {
+                [name, i7] => sheet.add(name, i7.as_bytes()),
+                [name, i7, i5] => sheet.add_dual(name, i7.as_bytes(), i5.as_bytes()),
+                _ => Err(anyhow!("expected a sample name and one or two barcodes")),
+            };
+            result.map_err(|why| anyhow!("Barcode sheet line {}: {}", idx + 1, why))?;
+        }
+        Ok(sheet)
+    }
+
+    /// Returns the sample names in the order they were added
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.samples.iter().map(|s| s.name.as_str())
+    }
+
+    /// Returns the number of samples
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Checks whether the sheet has no samples
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Checks whether the samples are dual indexed
+    #[must_use]
+    pub fn is_dual(&self) -> bool {
+        self.samples.first().is_some_and(|s| s.i5.is_some())
+    }
+}
+
+/// Where the barcode of a read is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeLocation {
+    /// The index field at the end of an Illumina header
+    /// (e.g. `1:N:0:ACGTAC` or `1:N:0:ACGTAC+GGTTCA` for dual indexes)
+    ///
+    /// Reads whose header has no such field are undetermined.
+    Header,
+    /// Inline at this 0-based position of the (first) read sequence
+    ///
+    /// Only single indexed sheets whose barcodes share a length can be
+    /// located inline.
+    Inline(usize),
+}
+
+/// The sample a read was assigned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assignment {
+    /// The index of the matching sample in the [`BarcodeSheet`]
+    Sample(usize),
+    /// Several samples match equally well
+    Ambiguous,
+    /// No sample matches within the mismatch tolerance
+    Undetermined,
+}
+
+/// Returns the number of mismatches between an observed barcode and a
+/// sample barcode, counting missing and extra bases as mismatches
+fn mismatches(observed: &[u8], expected: &[u8]) -> usize {
+    let differing = observed
+        .iter()
+        .zip(expected)
+        .filter(|(o, e)| o.to_ascii_uppercase() != **e)
+        .count();
+    differing + observed.len().abs_diff(expected.len())
+}
+
+/// Returns the i7 and optional i5 barcodes of an Illumina header or
+/// `None` if it has no `read:filtered:control:index` field
+fn header_barcodes(header: &[u8]) -> Option<(&[u8], Option<&[u8]>)> {
+    let comment = header
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|f| !f.is_empty())
+        .skip(1)
+        .last()?;
+    let fields: Vec<_> = comment.split(|b| *b == b':').collect();
+    match fields[..] {
+        [read, b"Y" | b"N", control, index]
+            if !read.is_empty()
+                && read.iter().all(u8::is_ascii_digit)
+                && !control.is_empty()
+                && control.iter().all(u8::is_ascii_digit) =>
+        {
+            let mut barcodes = index.splitn(2, |b| *b == b'+');
+            let i7 = barcodes.next().unwrap_or_default();
+            Some((i7, barcodes.next()))
+        }
+        _ => None,
+    }
+}
+
+/// Assigns reads to the samples of a [`BarcodeSheet`] and routes them
+/// to per-sample outputs.
+///
+/// Each barcode (i7 and i5 separately) may differ from the sample's by
+/// at most `max_mismatches` bases and reads equally close to several
+/// samples are ambiguous. Ambiguous and unmatched reads are written to
+/// the "undetermined" output.
+///
+/// ```
+/// use fxread::{Assignment, BarcodeLocation, BarcodeSheet, Demultiplexer, Record};
+///
+/// let mut sheet = BarcodeSheet::new();
+/// sheet.add("S1", b"ACGTAC").unwrap();
+/// sheet.add("S2", b"TTGGCA").unwrap();
+/// let demux = Demultiplexer::new(sheet, BarcodeLocation::Header).unwrap().max_mismatches(1);
+///
+/// let record = Record::new_fasta_from_parts(b"read.0 1:N:0:ACGTAT", b"ACGT").unwrap();
+/// assert_eq!(demux.assign(&record), Assignment::Sample(0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Demultiplexer {
+    sheet: BarcodeSheet,
+    location: BarcodeLocation,
+    max_mismatches: usize,
+    trim: bool,
+    counts: Vec<u64>,
+    ambiguous: u64,
+    undetermined: u64,
+}
+impl Demultiplexer {
+    /// Creates a new [`Demultiplexer`] allowing no mismatches
+    ///
+    /// Fails for inline barcodes if the sheet is dual indexed or its
+    /// barcodes differ in length.
+    pub fn new(sheet: BarcodeSheet, location: BarcodeLocation) -> Result<Self> {
+        if let BarcodeLocation::Inline(_) = location {
+            if sheet.is_dual() {
+                bail!("Dual indexed barcode sheets cannot be located inline");
+            }
+            if let Some(first) = sheet.samples.first() {
+                if let Some(other) = sheet.samples.iter().find(|s| s.i7.len() != first.i7.len()) {
+                    bail!(
+                        "Inline barcodes must share a length: {} ({}) and {} ({})",
+                        first.name,
+                        first.i7.len(),
+                        other.name,
+                        other.i7.len()
+                    );
+                }
+            }
+        }
+        let counts = vec![0; sheet.len()];
+        Ok(Self {
+            sheet,
+            location,
+            max_mismatches: 0,
+            trim: false,
+            counts,
+            ambiguous: 0,
+            undetermined: 0,
+        })
+    }
+
+    /// Sets the number of mismatches allowed in each barcode
+    #[must_use]
+    pub fn max_mismatches(mut self, max_mismatches: usize) -> Self {
+        self.max_mismatches = max_mismatches;
+        self
+    }
+
+    /// Sets whether inline barcodes (and the bases before them) are
+    /// trimmed from assigned reads
+    #[must_use]
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Returns the observed i7 and optional i5 barcodes of a read or
+    /// `None` if its header has no index
+    fn observed<'a>(&self, record: &'a Record) -> Option<(&'a [u8], Option<&'a [u8]>)> {
+        match self.location {
+            BarcodeLocation::Header => header_barcodes(record.id()),
+            BarcodeLocation::Inline(start) => {
+                let length = self.sheet.samples.first().map_or(0, |s| s.i7.len());
+                let seq = record.seq();
+                let start = start.min(seq.len());
+                Some((&seq[start..(start + length).min(seq.len())], None))
+            }
+        }
+    }
+
+    /// Assigns a read to a sample by its barcode
+    #[must_use]
+    pub fn assign(&self, record: &Record) -> Assignment {
+        let Some((i7, i5)) = self.observed(record) else {
+            return Assignment::Undetermined;
+        };
+        let mut best: Option<(usize, usize)> = None;
+        let mut tied = false;
+        for (idx, sample) in self.sheet.samples.iter().enumerate() {
+            let mut distance = mismatches(i7, &sample.i7);
+            if distance > self.max_mismatches {
+                continue;
+            }
+            if let Some(expected) = &sample.i5 {
+                let d5 = mismatches(i5.unwrap_or_default(), expected);
+                if d5 > self.max_mismatches {
+                    continue;
+                }
+                distance += d5;
+            }
+            match best {
+                Some((_, d)) if d < distance => {}
+                Some((_, d)) if d == distance => tied = true,
+                _ => {
+                    best = Some((idx, distance));
+                    tied = false;
+                }
+            }
+        }
+        match best {
+            Some(_) if tied => Assignment::Ambiguous,
+            Some((idx, _)) => Assignment::Sample(idx),
+            None => Assignment::Undetermined,
+        }
+    }
+
+    /// Returns the output index of a read (the sample or undetermined)
+    /// and updates the counts
+    fn route(&mut self, record: &Record) -> usize {
+        match self.assign(record) {
+            Assignment::Sample(idx) => {
+                self.counts[idx] += 1;
+                idx
+            }
+            Assignment::Ambiguous => {
+                self.ambiguous += 1;
+                self.sheet.len()
+            }
+            Assignment::Undetermined => {
+                self.undetermined += 1;
+                self.sheet.len()
+            }
+        }
+    }
+
+    /// Trims an inline barcode from an assigned read
+    fn trim_record(&self, record: &mut Record) -> Result<()> {
+        if let (true, BarcodeLocation::Inline(start)) = (self.trim, self.location) {
+            let length = self.sheet.samples.first().map_or(0, |s| s.i7.len());
+            record.trim_left((start + length).min(record.seq().len()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the output path of every sample followed by undetermined
+    fn paths(&self, template: &str, mate: Option<usize>) -> Vec<PathBuf> {
+        self.sheet
+            .names()
+            .chain(std::iter::once(UNDETERMINED))
+            .map(|name| {
+                let mut path = template.replace("{sample}", name);
+                if let Some(mate) = mate {
+                    path = path.replace("{mate}", &mate.to_string());
+                }
+                PathBuf::from(path)
+            })
+            .collect()
+    }
+
+    /// Checks a template and opens the writers of its paths
+    fn open(&self, template: &str, mate: Option<usize>) -> Result<Vec<PartWriter>> {
+        if !template.contains("{sample}") {
+            bail!(
+                "Demultiplexing template must contain '{{sample}}': {}",
+                template
+            );
+        }
+        let paths = self.paths(template, mate);
+        if paths.iter().collect::<HashSet<_>>().len() != paths.len() {
+            bail!("Sample names produce duplicate output paths");
+        }
+        paths.iter().map(|path| create_writer(path)).collect()
+    }
+
+    /// # Usage
+    /// Routes every read of `reader` to the output of its sample.
+    ///
+    /// Outputs are created for every sample and "undetermined" by
+    /// replacing `{sample}` in the template and are compressed according
+    /// to their extension.
+    /// ```
+    /// use fxread::{BarcodeLocation, BarcodeSheet, Demultiplexer, FastqReader};
+    ///
+    /// let mut sheet = BarcodeSheet::new();
+    /// sheet.add("S1", b"ACG").unwrap();
+    /// sheet.add("S2", b"TTT").unwrap();
+    /// let input: &'static [u8] = b"@r0\nACGAAA\n+\nIIIIII\n@r1\nGGGAAA\n+\nIIIIII\n";
+    ///
+    /// let template = std::env::temp_dir().join("fxread-doc-demux.{sample}.fq");
+    /// let mut demux = Demultiplexer::new(sheet, BarcodeLocation::Inline(0)).unwrap().trim(true);
+    /// let paths = demux.write(FastqReader::new(input), template.to_str().unwrap()).unwrap();
+    /// assert_eq!(std::fs::read(&paths[0]).unwrap(), b"@r0\nAAA\n+\nIII\n");
+    /// assert_eq!(demux.undetermined(), 1);
+    /// # paths.iter().for_each(|p| std::fs::remove_file(p).unwrap());
+    /// ```
+    pub fn write<R: FastxRead>(&mut self, mut reader: R, template: &str) -> Result<Vec<PathBuf>> {
+        let mut writers = self.open(template, None)?;
+        while let Some(mut record) = reader.next_record()? {
+            let output = self.route(&record);
+            if output < self.sheet.len() {
+                self.trim_record(&mut record)?;
+            }
+            writers[output].write_record(&record)?;
+        }
+        finish(writers)?;
+        Ok(self.paths(template, None))
+    }
+
+    /// Routes every pair of reads to the outputs of its sample.
+    ///
+    /// Barcodes are located in the first read of each pair and the
+    /// template must contain both `{sample}` and `{mate}`. Returns the
+    /// paths of both mates of every sample followed by undetermined.
+    pub fn write_paired<R1, R2>(
+        &mut self,
+        mut r1: R1,
+        mut r2: R2,
+        template: &str,
+    ) -> Result<Vec<(PathBuf, PathBuf)>>
+    where
+        R1: FastxRead,
+        R2: FastxRead,
+    {
+        if !template.contains("{mate}") {
+            bail!(
+                "Paired demultiplexing template must contain '{{mate}}': {}",
+                template
+            );
+        }
+        let mut first = self.open(template, Some(1))?;
+        let mut second = self.open(template, Some(2))?;
+        while let Some((mut a, b)) = next_pair(&mut r1, &mut r2)? {
+            let output = self.route(&a);
+            if output < self.sheet.len() {
+                self.trim_record(&mut a)?;
+            }
+            first[output].write_record(&a)?;
+            second[output].write_record(&b)?;
+        }
+        finish(first)?;
+        finish(second)?;
+        Ok(self
+            .paths(template, Some(1))
+            .into_iter()
+            .zip(self.paths(template, Some(2)))
+            .collect())
+    }
+
+    /// Returns the number of reads (or pairs) assigned to each sample
+    pub fn counts(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.sheet.names().zip(self.counts.iter().copied())
+    }
+
+    /// Returns the number of reads (or pairs) matching several samples
+    #[must_use]
+    pub fn ambiguous(&self) -> u64 {
+        self.ambiguous
+    }
+
+    /// Returns the number of reads (or pairs) matching no sample
+    #[must_use]
+    pub fn undetermined(&self) -> u64 {
+        self.undetermined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        header_barcodes, mismatches, Assignment, BarcodeLocation, BarcodeSheet, Demultiplexer,
+    };
+    use crate::{initialize_reader, FastaReader, Record};
+    use std::path::PathBuf;
+
+    fn fasta(header: &[u8], seq: &[u8]) -> Record {
+        Record::new_fasta_from_parts(header, seq).unwrap()
+    }
+
+    fn template(name: &str, pattern: &str) -> String {
+        let name = format!("fxread-demux-{}-{}.{}", std::process::id(), name, pattern);
+        std::env::temp_dir()
+            .join(name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Reads back and removes an output
+    fn read_ids(path: &PathBuf) -> Vec<String> {
+        let builder = crate::ReaderBuilder::new().allow_empty(true);
+        let ids = builder
+            .open(path)
+            .unwrap()
+            .map(|r: Record| r.id_str().split(' ').next().unwrap().to_string())
+            .collect();
+        std::fs::remove_file(path).unwrap();
+        ids
+    }
+
+    #[test]
+    fn parse_headers() {
+        let barcodes = header_barcodes(b"M001:1:FC:1:1101:1:1 1:N:0:ACGT+GGCC");
+        assert_eq!(
+            barcodes,
+            Some((b"ACGT".as_slice(), Some(b"GGCC".as_slice())))
+        );
+        let barcodes = header_barcodes(b"M001:1:FC:1:1101:1:1 1:N:0:ACGT");
+        assert_eq!(barcodes, Some((b"ACGT".as_slice(), None)));
+        assert_eq!(header_barcodes(b"r0"), None);
+        assert_eq!(header_barcodes(b"M001:1:FC:1:1101:1:1"), None);
+        assert_eq!(header_barcodes(b"r0 length=100"), None);
+    }
+
+    #[test]
+    fn count_mismatches() {
+        assert_eq!(mismatches(b"ACGT", b"ACGT"), 0);
+        assert_eq!(mismatches(b"acgt", b"ACGA"), 1);
+        assert_eq!(mismatches(b"ACNT", b"ACGT"), 1);
+        assert_eq!(mismatches(b"AC", b"ACGT"), 2);
+        assert_eq!(mismatches(b"ACGTTT", b"ACGT"), 2);
+    }
+
+    #[test]
+    fn sheet_validation() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"ACGT").unwrap();
+        assert!(sheet.add("S1", b"TTTT").is_err());
+        assert!(sheet.add("S2", b"AC-T").is_err());
+        assert!(sheet.add_dual("S3", b"ACGT", b"ACGT").is_err());
+        assert!(sheet.add("undetermined", b"GGGG").is_err());
+        assert!(!sheet.is_dual());
+        assert_eq!(sheet.len(), 1);
+    }
+
+    #[test]
+    fn inline_sheet_validation() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"ACGT").unwrap();
+        sheet.add("S2", b"ACGTTT").unwrap();
+        assert!(Demultiplexer::new(sheet.clone(), BarcodeLocation::Header).is_ok());
+        let err = Demultiplexer::new(sheet, BarcodeLocation::Inline(0))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("share a length"));
+
+        let mut sheet = BarcodeSheet::new();
+        sheet.add_dual("S1", b"ACGT", b"TTTT").unwrap();
+        assert!(Demultiplexer::new(sheet.clone(), BarcodeLocation::Header).is_ok());
+        assert!(Demultiplexer::new(sheet, BarcodeLocation::Inline(0)).is_err());
+    }
+
+    #[test]
+    fn sheet_from_file() {
+        let path = template("sheet", "csv");
+        std::fs::write(&path, "S1,ACGT,TTAA\n\nS2,GGCC,AATT\nS3,GGCC\n").unwrap();
+        let err = BarcodeSheet::from_file(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err.to_string().starts_with("Barcode sheet line 4: "));
+    }
+
+    #[test]
+    fn assignments() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"AAAA").unwrap();
+        sheet.add("S2", b"AATT").unwrap();
+        let demux = Demultiplexer::new(sheet, BarcodeLocation::Inline(2))
+            .unwrap()
+            .max_mismatches(1);
+        assert_eq!(
+            demux.assign(&fasta(b"r", b"CCAAAACC")),
+            Assignment::Sample(0)
+        );
+        assert_eq!(
+            demux.assign(&fasta(b"r", b"CCAATTCC")),
+            Assignment::Sample(1)
+        );
+        assert_eq!(
+            demux.assign(&fasta(b"r", b"CCAATACC")),
+            Assignment::Ambiguous
+        );
+        assert_eq!(
+            demux.assign(&fasta(b"r", b"CCGGGGCC")),
+            Assignment::Undetermined
+        );
+        assert_eq!(demux.assign(&fasta(b"r", b"CCAAA")), Assignment::Sample(0));
+        assert_eq!(demux.assign(&fasta(b"r", b"C")), Assignment::Undetermined);
+    }
+
+    #[test]
+    fn dual_index() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add_dual("S1", b"ACGT", b"TTTT").unwrap();
+        sheet.add_dual("S2", b"ACGT", b"GGGG").unwrap();
+        let demux = Demultiplexer::new(sheet, BarcodeLocation::Header)
+            .unwrap()
+            .max_mismatches(1);
+        let assign = |header: &[u8]| demux.assign(&fasta(header, b"A"));
+        assert_eq!(assign(b"r 1:N:0:ACGA+TTTG"), Assignment::Sample(0));
+        assert_eq!(assign(b"r 1:N:0:ACGT+GGGG"), Assignment::Sample(1));
+        assert_eq!(assign(b"r 1:N:0:AAAT+GGGG"), Assignment::Undetermined);
+        assert_eq!(assign(b"r 1:N:0:ACGT"), Assignment::Undetermined);
+        assert_eq!(assign(b"r"), Assignment::Undetermined);
+    }
+
+    #[test]
+    fn nested_barcodes() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"ACGT").unwrap();
+        sheet.add("S2", b"ACGTTT").unwrap();
+        let demux = Demultiplexer::new(sheet, BarcodeLocation::Header).unwrap();
+        let assign = |header: &[u8]| demux.assign(&fasta(header, b"A"));
+        assert_eq!(assign(b"r 1:N:0:ACGT"), Assignment::Sample(0));
+        assert_eq!(assign(b"r 1:N:0:ACGTTT"), Assignment::Sample(1));
+        assert_eq!(assign(b"r 1:N:0:ACGTT"), Assignment::Undetermined);
+        assert_eq!(assign(b"ACGT"), Assignment::Undetermined);
+    }
+
+    #[test]
+    fn write_outputs() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"AAAA").unwrap();
+        sheet.add("S2", b"CCCC").unwrap();
+        let input: &'static [u8] =
+            b">r0 1:N:0:AAAA\nA\n>r1 1:N:0:CCCG\nC\n>r2 1:N:0:GGGG\nG\n>r3 1:N:0:AAAT\nT\n";
+        let mut demux = Demultiplexer::new(sheet, BarcodeLocation::Header)
+            .unwrap()
+            .max_mismatches(1);
+        let paths = demux
+            .write(
+                FastaReader::new(input),
+                &template("single", "{sample}.fa.gz"),
+            )
+            .unwrap();
+        assert!(paths[2]
+            .to_str()
+            .unwrap()
+            .ends_with("single.undetermined.fa.gz"));
+        let ids: Vec<_> = paths.iter().map(read_ids).collect();
+        assert_eq!(ids, [vec!["r0", "r3"], vec!["r1"], vec!["r2"]]);
+        let counts: Vec<_> = demux.counts().collect();
+        assert_eq!(counts, [("S1", 2), ("S2", 1)]);
+        assert_eq!((demux.ambiguous(), demux.undetermined()), (0, 1));
+    }
+
+    #[test]
+    fn write_compressed_outputs() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"AAAA").unwrap();
+        sheet.add("S2", b"CCCC").unwrap();
+        let input: Vec<u8> = (0..3000)
+            .flat_map(|i| format!(">r{}\n{}GT\n", i, ["AAAA", "CCCC", "GGGG"][i % 3]).into_bytes())
+            .collect();
+        for ext in ["gz", "bz2", "xz", "zst"] {
+            let mut demux = Demultiplexer::new(sheet.clone(), BarcodeLocation::Inline(0)).unwrap();
+            let pattern = format!("{{sample}}.fa.{}", ext);
+            let paths = demux
+                .write(
+                    FastaReader::new(std::io::Cursor::new(input.clone())),
+                    &template("compressed", &pattern),
+                )
+                .unwrap();
+            let sizes: Vec<_> = paths.iter().map(|p| read_ids(p).len()).collect();
+            assert_eq!(sizes, [1000, 1000, 1000]);
+        }
+    }
+
+    #[test]
+    fn write_paired_outputs() {
+        let mut sheet = BarcodeSheet::new();
+        sheet.add("S1", b"AC").unwrap();
+        let r1: &'static [u8] = b">p0\nACGG\n>p1\nTTGG\n";
+        let r2: &'static [u8] = b">p0\nCCCC\n>p1\nAAAA\n";
+        let mut demux = Demultiplexer::new(sheet.clone(), BarcodeLocation::Inline(0))
+            .unwrap()
+            .trim(true);
+        let paths = demux
+            .write_paired(
+                FastaReader::new(r1),
+                FastaReader::new(r2),
+                &template("paired", "{sample}_R{mate}.fa"),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 2);
+        let trimmed = initialize_reader(&paths[0].0).unwrap().next().unwrap();
+        assert_eq!(trimmed.seq(), b"GG");
+        for (a, b) in &paths {
+            assert_eq!(read_ids(a), read_ids(b));
+        }
+
+        let mut demux = Demultiplexer::new(sheet, BarcodeLocation::Inline(0)).unwrap();
+        let err = demux
+            .write_paired(
+                FastaReader::new(r1),
+                FastaReader::new(r2),
+                "out.{sample}.fa",
+            )
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("{mate}"));
+    }
+}
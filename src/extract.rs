@@ -0,0 +1,311 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use super::search::header_id;
+use super::ReaderBuilder;
+use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+/// Removes a trailing `/1` or `/2` mate suffix from an ID
+fn strip_mate(id: &[u8]) -> &[u8] {
+    match id {
+        [rest @ .., b'/', b'1' | b'2'] => rest,
+        _ => id,
+    }
+}
+
+/// A reader adaptor which extracts (or excludes) records by ID.
+///
+/// IDs are compared against the first whitespace delimited token of
+/// each header. By default every ID is expected to match a single record:
+/// only the first record of each ID is yielded and reading stops as soon
+/// as every ID has been found. Disable [`ExtractReader::unique`] to read
+/// the whole input and yield every record of a matching ID (e.g. both
+/// mates of interleaved pairs with [`ExtractReader::strip_mate`] or
+/// repeated IDs).
+///
+/// ```
+/// use fxread::{initialize_reader, ExtractReader};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let mut extract = ExtractReader::new(reader, ["seq.3", "seq.7", "seq.missing"]);
+/// assert_eq!(extract.by_ref().count(), 2);
+///
+/// let missing: Vec<_> = extract.missing().unwrap().collect();
+/// assert_eq!(missing, [b"seq.missing".as_slice()]);
+/// ```
+pub struct ExtractReader<R: FastxRead> {
+    reader: R,
+    /// Every ID and whether a record of it has been found
+    ids: HashMap<Vec<u8>, bool>,
+    /// The number of IDs without a record found so far
+    remaining: usize,
+    strip_mate: bool,
+    exclude: bool,
+    unique: bool,
+    found: u64,
+}
+impl<R: FastxRead> ExtractReader<R> {
+    /// Creates a new [`ExtractReader`] yielding the records of `ids`
+    pub fn new<I, T>(reader: R, ids: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let ids: HashMap<_, _> = ids
+            .into_iter()
+            .map(|id| (id.as_ref().to_vec(), false))
+            .collect();
+        Self {
+            reader,
+            remaining: ids.len(),
+            ids,
+            strip_mate: false,
+            exclude: false,
+            unique: true,
+            found: 0,
+        }
+    }
+
+    /// # Usage
+    /// Creates a new [`ExtractReader`] with IDs read from a (possibly
+    /// compressed) file.
+    ///
+    /// The first whitespace delimited token of every line is used as an
+    /// ID after removing any leading '@' or '>' so that headers can be
+    /// used directly. Blank lines are skipped.
+    /// ```
+    /// use fxread::{initialize_reader, ExtractReader};
+    ///
+    /// let reader = initialize_reader("example/sequences.fa").unwrap();
+    /// let extract = ExtractReader::from_file(reader, "example/sequences.fa").unwrap();
+    /// assert_eq!(extract.count(), 10);
+    /// ```
+    pub fn from_file<P: AsRef<Path>>(reader: R, path: P) -> Result<Self> {
+        let input = ReaderBuilder::new().decompress(File::open(path)?)?;
+        let mut ids = HashSet::new();
+        for line in BufReader::new(input).split(b'\n') {
+            let line = line?;
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            let line = match line {
+                [b'@' | b'>', rest @ ..] => rest,
+                _ => line,
+            };
+            let start = line
+                .iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .unwrap_or(line.len());
+            let id = header_id(&line[start..]);
+            if !id.is_empty() {
+                ids.insert(id.to_vec());
+            }
+        }
+        Ok(Self::new(reader, ids))
+    }
+
+    /// Sets whether a trailing `/1` or `/2` is removed from IDs (both
+    /// the provided IDs and the record IDs) before comparing them
+    #[must_use]
+    pub fn strip_mate(mut self, strip: bool) -> Self {
+        self.strip_mate = strip;
+        if strip {
+            self.ids = self
+                .ids
+                .keys()
+                .map(|id| (strip_mate(id).to_vec(), false))
+                .collect();
+            self.remaining = self.ids.len();
+        }
+        self
+    }
+
+    /// Sets whether records of the IDs are excluded instead of
+    /// extracted
+    #[must_use]
+    pub fn exclude(mut self, exclude: bool) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// # Usage
+    /// Sets whether every ID matches at most one record, which stops
+    /// extraction as soon as every ID has been found (the default).
+    ///
+    /// Records of an ID after its first are skipped, so this must be
+    /// disabled to extract both mates of interleaved pairs with
+    /// [`ExtractReader::strip_mate`]. Has no effect when excluding.
+    /// ```
+    /// use fxread::{ExtractReader, FastaReader, FastxRead};
+    ///
+    /// let fasta: &'static [u8] = b">r1/1\nA\n>r1/2\nC\n>r2/1\nG\n";
+    /// let mut extract = ExtractReader::new(FastaReader::new(fasta), ["r1"]).strip_mate(true);
+    /// assert!(extract.next_record().unwrap().is_some());
+    /// assert!(extract.next_record().unwrap().is_none());
+    ///
+    /// let extract = ExtractReader::new(FastaReader::new(fasta), ["r1"])
+    ///     .strip_mate(true)
+    ///     .unique(false);
+    /// assert_eq!(extract.count(), 2);
+    /// ```
+    #[must_use]
+    pub fn unique(mut self, unique: bool) -> Self {
+        self.unique = unique;
+        self
+    }
+
+    /// Returns the number of records whose ID was in the set
+    #[must_use]
+    pub fn found(&self) -> u64 {
+        self.found
+    }
+
+    /// Returns the IDs no record has been found for so far, or `None`
+    /// when excluding
+    pub fn missing(&self) -> Option<impl Iterator<Item = &[u8]>> {
+        (!self.exclude).then(|| {
+            self.ids
+                .iter()
+                .filter(|(_, found)| !**found)
+                .map(|(id, _)| id.as_slice())
+        })
+    }
+}
+
+impl<R: FastxRead> FastxRead for ExtractReader<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        let stop_early = self.unique && !self.exclude;
+        if stop_early && self.remaining == 0 {
+            return Ok(None);
+        }
+        while let Some(record) = self.reader.next_record()? {
+            let id = header_id(record.id());
+            let id = if self.strip_mate { strip_mate(id) } else { id };
+            match self.ids.get_mut(id) {
+                Some(true) if stop_early => {}
+                Some(found) => {
+                    if !*found {
+                        *found = true;
+                        self.remaining -= 1;
+                    }
+                    self.found += 1;
+                    if !self.exclude {
+                        return Ok(Some(record));
+                    }
+                }
+                None if self.exclude => return Ok(Some(record)),
+                None => {}
+            }
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for ExtractReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip_mate, ExtractReader};
+    use crate::{FastqReader, FastxRead, Record};
+    use std::io::Write;
+
+    const FASTQ: &[u8] =
+        b"@r1/1 x\nA\n+\nI\n@r2/1\nC\n+\nI\n@r3/1\nG\n+\nI\n@r2/1 dup\nT\n+\nI\n@r4/1\nT\n+\nI\n";
+
+    fn ids<R: Iterator<Item = Record>>(reader: R) -> Vec<String> {
+        reader
+            .map(|r| r.id_str().split(' ').next().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn strip_mates() {
+        assert_eq!(strip_mate(b"r1/1"), b"r1");
+        assert_eq!(strip_mate(b"r1/2"), b"r1");
+        assert_eq!(strip_mate(b"r1/3"), b"r1/3");
+        assert_eq!(strip_mate(b"/1"), b"");
+    }
+
+    #[test]
+    fn extract_with_mates() {
+        let reader = ExtractReader::new(FastqReader::new(FASTQ), ["r2/2", "r1"]);
+        assert!(ids(reader).is_empty());
+
+        let reader = ExtractReader::new(FastqReader::new(FASTQ), ["r2/2", "r1"]).strip_mate(true);
+        assert_eq!(ids(reader), ["r1/1", "r2/1"]);
+
+        let reader = ExtractReader::new(FastqReader::new(FASTQ), ["r2/2", "r1"])
+            .strip_mate(true)
+            .unique(false);
+        assert_eq!(ids(reader), ["r1/1", "r2/1", "r2/1"]);
+    }
+
+    #[test]
+    fn interleaved_pairs() {
+        let fastq: &'static [u8] =
+            b"@p1/1\nA\n+\nI\n@p1/2\nC\n+\nI\n@p2/1\nG\n+\nI\n@p2/2\nT\n+\nI\n";
+        let mut reader = ExtractReader::new(FastqReader::new(fastq), ["p2", "p1"])
+            .strip_mate(true)
+            .unique(false);
+        assert_eq!(ids(reader.by_ref()), ["p1/1", "p1/2", "p2/1", "p2/2"]);
+        assert_eq!(reader.found(), 4);
+        assert_eq!(reader.missing().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn stops_early() {
+        // the malformed final record is never reached
+        let fastq: &'static [u8] = b"@r1\nA\n+\nI\n@r2\nC\n+\nI\nr3\nG\n+\nI\n";
+        let mut reader = ExtractReader::new(FastqReader::new(fastq), ["r2"]);
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().unwrap().is_none());
+        assert_eq!(reader.found(), 1);
+
+        // without unique IDs the whole input is read
+        let mut reader = ExtractReader::new(FastqReader::new(fastq), ["r2"]).unique(false);
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().is_err());
+
+        // later records of an ID are skipped
+        let reader = ExtractReader::new(FastqReader::new(FASTQ), ["r2/1"]);
+        assert_eq!(ids(reader), ["r2/1"]);
+    }
+
+    #[test]
+    fn exclude() {
+        let mut reader =
+            ExtractReader::new(FastqReader::new(FASTQ), ["r2/1", "r9/1"]).exclude(true);
+        assert_eq!(ids(reader.by_ref()), ["r1/1", "r3/1", "r4/1"]);
+        assert_eq!(reader.found(), 2);
+        assert!(reader.missing().is_none());
+    }
+
+    #[test]
+    fn ids_from_file() {
+        let path =
+            std::env::temp_dir().join(format!("fxread-extract-{}-ids.txt", std::process::id()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.write_all(b"@r4/1 comment\r\n\n  r1/2\n>r3\n").unwrap();
+        let reader = ExtractReader::from_file(FastqReader::new(FASTQ), &path)
+            .unwrap()
+            .strip_mate(true);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ids(reader), ["r1/1", "r3/1", "r4/1"]);
+    }
+}
@@ -1,10 +1,15 @@
-use super::fastx::FastxRead;
+use super::fastx::{FastxRead, Format};
+use super::parse::{fasta_record, read_lines};
 use super::record::Record;
 use anyhow::{anyhow, Result};
 use std::io::BufRead;
 
 /// Struct to handle the Byte Reading for Fasta Formatted Files.
 /// Heavily inspired from bstr `ByteRecord`.
+///
+/// Records are delimited by their line structure: each record is a header
+/// line which must begin with '>' followed by a single sequence line, so a
+/// '>' appearing elsewhere in the header is treated as part of the ID.
 pub struct FastaBytes<B> {
     buf: B,
 }
@@ -14,25 +19,18 @@ impl<B: BufRead> Iterator for FastaBytes<B> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut bytes = Vec::with_capacity(300);
+        let mut lines = [0; 2];
 
-        match self.buf.read_until(b'>', &mut bytes) {
+        let num_lines = match read_lines(&mut self.buf, &mut bytes, &mut lines) {
             Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(1) => {}
-            Ok(_) => return Some(Err(anyhow!("Misplaced Fasta Marker Sequence '>'"))),
-        };
-        let id = match self.buf.read_until(b'\n', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(x) => x,
-        };
-        let seq = match self.buf.read_until(b'\n', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
             Ok(x) => x,
         };
-        let record = Record::new_fasta(bytes, id, seq);
-        Some(Ok(record))
+        let buf = &mut self.buf;
+        fasta_record(
+            bytes,
+            &lines[..num_lines],
+            || matches!(buf.fill_buf(), Ok(rest) if rest.is_empty()),
+        )
     }
 }
 
@@ -79,6 +77,10 @@ impl<R: BufRead> FastxRead for FastaReader<R> {
         };
         Ok(Some(buffer))
     }
+
+    fn format(&self) -> Option<Format> {
+        Some(Format::Fasta)
+    }
 }
 
 impl<R: BufRead> Iterator for FastaReader<R> {
@@ -95,6 +97,7 @@ impl<R: BufRead> Iterator for FastaReader<R> {
 #[cfg(test)]
 mod tests {
     use super::FastaReader;
+    use crate::FastxRead;
     use flate2::read::MultiGzDecoder;
     use std::fs::File;
     use std::io::BufReader;
@@ -108,7 +111,36 @@ mod tests {
         assert_eq!(record.as_ref().unwrap().id(), b"seq.id");
         assert_eq!(record.as_ref().unwrap().seq(), b"ACGT");
         assert_eq!(record.as_ref().unwrap().data(), b">seq.id\nACGT\n");
-        assert_eq!(reader.into_iter().count(), 0);
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[test]
+    fn marker_in_header() {
+        let fasta: &'static [u8] = b">seq>0 desc>1\nACGT\n>seq.1\nTTTT\n";
+        let records: Vec<_> = FastaReader::new(fasta).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id(), b"seq>0 desc>1");
+        assert_eq!(records[0].seq(), b"ACGT");
+        assert_eq!(records[1].id(), b"seq.1");
+    }
+
+    #[test]
+    fn misplaced_marker() {
+        let fasta: &'static [u8] = b">seq.0\nACGT\nseq.1\n>ACGT\n";
+        let mut reader = FastaReader::new(fasta);
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().is_err());
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let fasta: &'static [u8] = b">seq.0\r\nACGT\r\n>seq.1\r\nTTTT\r\n";
+        let records: Vec<_> = FastaReader::new(fasta).collect();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.valid()));
+        assert_eq!(records[0].id(), b"seq.0");
+        assert_eq!(records[0].seq(), b"ACGT");
+        assert_eq!(records[1].data(), b">seq.1\nTTTT\n");
     }
 
     #[test]
@@ -127,6 +159,24 @@ mod tests {
         assert_eq!(record.seq_upper(), b"ACGT");
     }
 
+    #[test]
+    fn unterminated_final_line() {
+        let fasta: &'static [u8] = b">seq.0\nACGT\n>seq.1\nTT";
+        let records: Vec<_> = FastaReader::new(fasta).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seq(), b"TT");
+        assert_eq!(records[1].data(), b">seq.1\nTT\n");
+    }
+
+    #[test]
+    fn small_buffer() {
+        let file = File::open("example/sequences.fa").unwrap();
+        let buffer = BufReader::with_capacity(7, file);
+        let records: Vec<_> = FastaReader::new(buffer).collect();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[9].id(), b"seq.9");
+    }
+
     #[test]
     fn read_plaintext() {
         let file = File::open("example/sequences.fa").unwrap();
@@ -139,7 +189,7 @@ mod tests {
             record.as_ref().unwrap().seq(),
             b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA"
         );
-        assert_eq!(reader.into_iter().count(), 9);
+        assert_eq!(reader.count(), 9);
     }
 
     #[test]
@@ -155,6 +205,6 @@ mod tests {
             record.as_ref().unwrap().seq(),
             b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA"
         );
-        assert_eq!(reader.into_iter().count(), 9);
+        assert_eq!(reader.count(), 9);
     }
 }
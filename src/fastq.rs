@@ -1,13 +1,38 @@
 use anyhow::{anyhow, Result};
 use std::io::BufRead;
 
-use super::fastx::FastxRead;
+use super::fastx::{FastxRead, Format};
+use super::parse::{fastq_record, read_lines};
 use super::record::Record;
 
-/// Struct to handle the Byte Reading for Fasta Formatted Files.
+/// The level of validation applied when parsing fastq records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Records are split on their line layout without further checks
+    /// and a truncated final record ends the iteration.
+    #[default]
+    Lenient,
+    /// The third line must begin with '+' and any name following it must
+    /// match the header, the sequence and quality lengths must match, and
+    /// a truncated final record is an error.
+    Standard,
+    /// Applies all [`Strictness::Standard`] checks and additionally requires
+    /// every quality character to be within the printable range '!'..='~'.
+    Strict,
+}
+
+/// Struct to handle the Byte Reading for Fastq Formatted Files.
 /// Heavily inspired from bstr `ByteRecord`.
+///
+/// Records are delimited by their fixed four line layout: a header line
+/// which must begin with '@', the sequence, the '+' line, and the quality
+/// scores. An '@' in the header or at the start of a quality line (a valid
+/// Phred score of 31) is never treated as the start of a record.
 pub struct FastqBytes<B> {
     buf: B,
+    strictness: Strictness,
+    /// The number of records parsed so far
+    count: usize,
 }
 
 impl<B: BufRead> Iterator for FastqBytes<B> {
@@ -15,35 +40,23 @@ impl<B: BufRead> Iterator for FastqBytes<B> {
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut bytes = Vec::with_capacity(300);
+        let mut lines = [0; 4];
 
-        match self.buf.read_until(b'@', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(1) => {}
-            Ok(_) => return Some(Err(anyhow!("Misplaced Fastq Marker Sequence '@'"))),
-        };
-        let id = match self.buf.read_until(b'\n', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(x) => x,
-        };
-        let seq = match self.buf.read_until(b'\n', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(x) => x,
-        };
-        let plus = match self.buf.read_until(b'\n', &mut bytes) {
+        let num_lines = match read_lines(&mut self.buf, &mut bytes, &mut lines) {
             Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
             Ok(x) => x,
         };
-        let qual = match self.buf.read_until(b'\n', &mut bytes) {
-            Err(why) => return Some(Err(anyhow!(why))),
-            Ok(0) => return None,
-            Ok(x) => x,
-        };
-        let record = Record::new_fastq(bytes, id, seq, plus, qual);
-        Some(Ok(record))
+        if num_lines > 0 {
+            self.count += 1;
+        }
+        let buf = &mut self.buf;
+        fastq_record(
+            bytes,
+            &lines[..num_lines],
+            self.strictness,
+            self.count,
+            || matches!(buf.fill_buf(), Ok(rest) if rest.is_empty()),
+        )
     }
 }
 
@@ -67,8 +80,27 @@ impl<R: BufRead> FastqReader<R> {
     /// let reader = fxread::FastqReader::new(buffer);
     /// ```
     pub fn new(reader: R) -> Self {
+        Self::with_strictness(reader, Strictness::default())
+    }
+
+    /// # Example
+    /// Creates a new [`FastqReader`] which validates each record
+    /// at the provided [`Strictness`].
+    ///
+    /// ```
+    /// use fxread::{FastqReader, FastxRead, Strictness};
+    ///
+    /// let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n123\n";
+    /// let mut reader = FastqReader::with_strictness(fastq, Strictness::Standard);
+    /// assert!(reader.next_record().is_err());
+    /// ```
+    pub fn with_strictness(reader: R, strictness: Strictness) -> Self {
         Self {
-            reader: FastqBytes { buf: reader },
+            reader: FastqBytes {
+                buf: reader,
+                strictness,
+                count: 0,
+            },
         }
     }
 
@@ -89,6 +121,10 @@ impl<R: BufRead> FastxRead for FastqReader<R> {
         };
         Ok(Some(buffer))
     }
+
+    fn format(&self) -> Option<Format> {
+        Some(Format::Fastq)
+    }
 }
 
 impl<R: BufRead> Iterator for FastqReader<R> {
@@ -104,7 +140,8 @@ impl<R: BufRead> Iterator for FastqReader<R> {
 
 #[cfg(test)]
 mod tests {
-    use super::FastqReader;
+    use super::{FastqReader, Strictness};
+    use crate::FastxRead;
     use flate2::read::MultiGzDecoder;
     use std::fs::File;
     use std::io::BufReader;
@@ -118,7 +155,47 @@ mod tests {
         assert_eq!(record.as_ref().unwrap().id(), b"seq.id");
         assert_eq!(record.as_ref().unwrap().seq(), b"ACGT");
         assert_eq!(record.as_ref().unwrap().data(), b"@seq.id\nACGT\n+\n7162\n");
-        assert_eq!(reader.into_iter().count(), 0);
+        assert_eq!(reader.count(), 0);
+    }
+
+    #[test]
+    fn marker_in_quality() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n@@@@\n@seq@1\nTTTT\n+\n@F@F\n";
+        let records: Vec<_> = FastqReader::new(fastq).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].qual().unwrap(), b"@@@@");
+        assert_eq!(records[1].id(), b"seq@1");
+        assert_eq!(records[1].seq(), b"TTTT");
+        assert_eq!(records[1].qual().unwrap(), b"@F@F");
+    }
+
+    #[test]
+    fn marker_across_buffers() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n@@@@\n@seq.1\nTTTT\n+\n@@@@\n";
+        let buffer = BufReader::with_capacity(2, fastq);
+        let records: Vec<_> = FastqReader::new(buffer).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].id(), b"seq.1");
+    }
+
+    #[test]
+    fn misplaced_marker() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\nseq.1\nACGT\n+\n1234\n";
+        let mut reader = FastqReader::new(fastq);
+        assert!(reader.next_record().unwrap().is_some());
+        assert!(reader.next_record().is_err());
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let fastq: &'static [u8] = b"@seq.0\r\nACGT\r\n+\r\n1234\r\n@seq.1\r\nTTTT\r\n+\r\n5678";
+        let records: Vec<_> = FastqReader::new(fastq).collect();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.valid()));
+        assert_eq!(records[0].seq().len(), records[0].qual().unwrap().len());
+        assert_eq!(records[0].qual().unwrap(), b"1234");
+        assert_eq!(records[1].qual().unwrap(), b"5678");
+        assert_eq!(records[1].data(), b"@seq.1\nTTTT\n+\n5678\n");
     }
 
     #[test]
@@ -137,6 +214,95 @@ mod tests {
         assert_eq!(record.seq_upper(), b"ACGT");
     }
 
+    #[test]
+    fn unterminated_final_line() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nTT\n+\n12";
+        let records: Vec<_> = FastqReader::new(fastq).collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seq(), b"TT");
+        assert_eq!(records[1].qual().unwrap(), b"12");
+        assert_eq!(records[1].data(), b"@seq.1\nTT\n+\n12\n");
+    }
+
+    #[test]
+    fn small_buffer() {
+        let file = File::open("example/sequences.fq").unwrap();
+        let buffer = BufReader::with_capacity(7, file);
+        let records: Vec<_> = FastqReader::new(buffer).collect();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[9].id(), b"seq.9");
+    }
+
+    fn strict_error(fastq: &'static [u8], strictness: Strictness) -> String {
+        let mut reader = FastqReader::with_strictness(fastq, strictness);
+        reader.next_record().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn lenient_accepts_malformed() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n-\n123\n@seq.1\nACGT\n";
+        let records: Vec<_> = FastqReader::new(fastq).collect();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn standard_accepts_valid() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+seq.0\n1234\n@seq.1\nAC\n+\n12\n";
+        let reader = FastqReader::with_strictness(fastq, Strictness::Strict);
+        assert_eq!(reader.count(), 2);
+    }
+
+    #[test]
+    fn standard_missing_plus() {
+        let why = strict_error(b"@seq.0\nACGT\n-\n1234\n", Strictness::Standard);
+        assert_eq!(
+            why,
+            "Record 1: expected '+' at the start of the third line but found '-'"
+        );
+    }
+
+    #[test]
+    fn standard_plus_name_mismatch() {
+        let why = strict_error(b"@seq.0\nACGT\n+seq.1\n1234\n", Strictness::Standard);
+        assert_eq!(
+            why,
+            "Record 1: name after '+' (seq.1) does not match the header (seq.0)"
+        );
+    }
+
+    #[test]
+    fn standard_length_mismatch() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nACGT\n+\n123\n";
+        let mut reader = FastqReader::with_strictness(fastq, Strictness::Standard);
+        assert!(reader.next_record().unwrap().is_some());
+        assert_eq!(
+            reader.next_record().unwrap_err().to_string(),
+            "Record 2: sequence length (4) does not match quality length (3)"
+        );
+    }
+
+    #[test]
+    fn standard_truncated() {
+        let why = strict_error(b"@seq.0\nACGT\n", Strictness::Standard);
+        assert_eq!(
+            why,
+            "Record 1: truncated record (expected 4 lines but found 2)"
+        );
+    }
+
+    #[test]
+    fn standard_allows_qual_range() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n12 4\n";
+        let mut reader = FastqReader::with_strictness(fastq, Strictness::Standard);
+        assert!(reader.next_record().unwrap().is_some());
+    }
+
+    #[test]
+    fn strict_qual_range() {
+        let why = strict_error(b"@seq.0\nACGT\n+\n12 4\n", Strictness::Strict);
+        assert_eq!(why, "Record 1: invalid quality character ' ' at position 3");
+    }
+
     #[test]
     fn read_plaintext() {
         let file = File::open("example/sequences.fq").unwrap();
@@ -149,7 +315,7 @@ mod tests {
             record.as_ref().unwrap().seq(),
             b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA"
         );
-        assert_eq!(reader.into_iter().count(), 9);
+        assert_eq!(reader.count(), 9);
     }
 
     #[test]
@@ -165,6 +331,6 @@ mod tests {
             record.as_ref().unwrap().seq(),
             b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA"
         );
-        assert_eq!(reader.into_iter().count(), 9);
+        assert_eq!(reader.count(), 9);
     }
 }
@@ -1,8 +1,71 @@
 use super::Record;
 use anyhow::Result;
 
+/// The format of a fastx file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// Records marked by '>' with an id and sequence line
+    Fasta,
+    /// Records marked by '@' with an id, sequence, plus, and quality line
+    Fastq,
+}
+impl Format {
+    /// Determines the format from the first character of a record
+    #[must_use]
+    pub fn from_marker(marker: u8) -> Option<Self> {
+        match marker {
+            b'>' => Some(Self::Fasta),
+            b'@' => Some(Self::Fastq),
+            _ => None,
+        }
+    }
+}
+
 /// A trait for Fasta and Fastq readers
 pub trait FastxRead: Iterator {
     /// Returns the next fastx [`Record`] in the iterator.
     fn next_record(&mut self) -> Result<Option<Record>>;
+
+    /// Returns the [`Format`] of the records or `None` if it is unknown
+    /// (e.g. for an empty input).
+    fn format(&self) -> Option<Format> {
+        None
+    }
+}
+
+impl<T: FastxRead + ?Sized> FastxRead for Box<T> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        (**self).next_record()
+    }
+
+    fn format(&self) -> Option<Format> {
+        (**self).format()
+    }
+}
+
+/// A reader over an input without any records.
+///
+/// Yields no records and reports an unknown format.
+/// ```
+/// use fxread::{EmptyReader, FastxRead};
+///
+/// let mut reader = EmptyReader;
+/// assert!(reader.next_record().unwrap().is_none());
+/// assert!(reader.format().is_none());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmptyReader;
+
+impl FastxRead for EmptyReader {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        Ok(None)
+    }
+}
+
+impl Iterator for EmptyReader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        None
+    }
 }
@@ -0,0 +1,407 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use anyhow::Result;
+#[cfg(feature = "regex")]
+use regex::bytes::Regex;
+use std::{fmt, sync::OnceLock};
+
+/// The offset of Phred+33 encoded quality scores
+const PHRED_OFFSET: u8 = 33;
+
+/// Returns the error probability of a Phred+33 encoded quality character
+fn error_probability(qual: u8) -> f64 {
+    static TABLE: OnceLock<[f64; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [1.0; 256];
+        for (q, p) in table.iter_mut().enumerate().skip(PHRED_OFFSET as usize) {
+            *p = 10f64.powf(-((q - PHRED_OFFSET as usize) as f64) / 10.0);
+        }
+        table
+    })[qual as usize]
+}
+
+/// The length of the windows scored by [`dust_score`]
+const DUST_WINDOW: usize = 64;
+
+/// Returns the DUST score of a single window.
+///
+/// Each of the `l` trinucleotides occurring `c` times contributes
+/// `c * (c - 1) / 2` and the sum is divided by `l - 1`.
+fn dust_window(seq: &[u8]) -> f64 {
+    if seq.len() < 4 {
+        return 0.0;
+    }
+    let mut counts = [0u32; 64];
+    let mut triplets = 0u32;
+    for window in seq.windows(3) {
+        let idx = window.iter().try_fold(0, |idx, base| {
+            let code = match base {
+                b'A' | b'a' => 0,
+                b'C' | b'c' => 1,
+                b'G' | b'g' => 2,
+                b'T' | b't' => 3,
+                _ => return None,
+            };
+            Some(idx << 2 | code)
+        });
+        if let Some(idx) = idx {
+            counts[idx] += 1;
+            triplets += 1;
+        }
+    }
+    if triplets < 2 {
+        return 0.0;
+    }
+    let sum: u64 = counts
+        .iter()
+        .map(|c| u64::from(*c) * u64::from(c.saturating_sub(1)) / 2)
+        .sum();
+    sum as f64 / f64::from(triplets - 1)
+}
+
+/// Returns the DUST score of a sequence: the highest score of its
+/// windows of [`DUST_WINDOW`] bases overlapping by half.
+///
+/// Scoring windows keeps the score independent of the read length.
+/// Random sequence scores below 2 while homopolymers of length `n`
+/// score about `n / 2` (at most 31 for a whole window).
+fn dust_score(seq: &[u8]) -> f64 {
+    if seq.len() <= DUST_WINDOW {
+        return dust_window(seq);
+    }
+    let last = seq.len() - DUST_WINDOW;
+    (0..last)
+        .step_by(DUST_WINDOW / 2)
+        .chain(std::iter::once(last))
+        .map(|start| dust_window(&seq[start..start + DUST_WINDOW]))
+        .fold(0.0, f64::max)
+}
+
+/// A condition which records must satisfy to be kept.
+///
+/// Quality based filters only apply to records with quality scores
+/// (Phred+33 encoded) and always keep fasta records.
+///
+/// Its variants depend on the enabled features (`HeaderRegex` requires
+/// `regex`) so matches on it need a wildcard arm.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Filter {
+    /// Keeps records with at least this many bases
+    MinLength(usize),
+    /// Keeps records with at most this many bases
+    MaxLength(usize),
+    /// Keeps records whose mean quality score is at least this value
+    MinMeanQuality(f64),
+    /// Keeps records whose expected number of errors (the sum of the
+    /// error probabilities of each base) is at most this value
+    MaxExpectedErrors(f64),
+    /// Keeps records whose fraction of 'N' bases is at most this value
+    MaxNFraction(f64),
+    /// Keeps records whose DUST score is at most this value
+    ///
+    /// The score is the highest of the read's 64 base windows so it does
+    /// not grow with the read length. Homopolymers and short tandem
+    /// repeats have high scores while random sequence scores below 2.
+    LowComplexity(f64),
+    /// Keeps records whose header matches the regular expression
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    HeaderRegex(Regex),
+}
+impl Filter {
+    /// # Usage
+    /// Checks whether a record satisfies the filter
+    /// ```
+    /// use fxread::{Filter, Record};
+    ///
+    /// let record = Record::new_fastq_from_parts(b"seq.0", b"ACNT", b"II#I").unwrap();
+    /// assert!(Filter::MinLength(4).keep(&record));
+    /// assert!(!Filter::MaxNFraction(0.1).keep(&record));
+    /// assert!(Filter::MaxExpectedErrors(1.0).keep(&record));
+    /// ```
+    #[must_use]
+    pub fn keep(&self, record: &Record) -> bool {
+        let seq = record.seq();
+        match self {
+            Filter::MinLength(min) => seq.len() >= *min,
+            Filter::MaxLength(max) => seq.len() <= *max,
+            Filter::MinMeanQuality(min) => match record.qual() {
+                Some(qual) if !qual.is_empty() => {
+                    let sum: u64 = qual
+                        .iter()
+                        .map(|q| u64::from(q.saturating_sub(PHRED_OFFSET)))
+                        .sum();
+                    sum as f64 / qual.len() as f64 >= *min
+                }
+                _ => true,
+            },
+            Filter::MaxExpectedErrors(max) => match record.qual() {
+                Some(qual) => qual.iter().map(|q| error_probability(*q)).sum::<f64>() <= *max,
+                None => true,
+            },
+            Filter::MaxNFraction(max) => {
+                if seq.is_empty() {
+                    return true;
+                }
+                let n = seq.iter().filter(|b| matches!(b, b'N' | b'n')).count();
+                n as f64 / seq.len() as f64 <= *max
+            }
+            Filter::LowComplexity(max) => dust_score(seq) <= *max,
+            #[cfg(feature = "regex")]
+            Filter::HeaderRegex(regex) => regex.is_match(record.id()),
+        }
+    }
+}
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::MinLength(x) => write!(f, "min_length({})", x),
+            Filter::MaxLength(x) => write!(f, "max_length({})", x),
+            Filter::MinMeanQuality(x) => write!(f, "min_mean_quality({})", x),
+            Filter::MaxExpectedErrors(x) => write!(f, "max_expected_errors({})", x),
+            Filter::MaxNFraction(x) => write!(f, "max_n_fraction({})", x),
+            Filter::LowComplexity(x) => write!(f, "low_complexity({})", x),
+            #[cfg(feature = "regex")]
+            Filter::HeaderRegex(x) => write!(f, "header_regex({})", x),
+        }
+    }
+}
+
+/// A reader adaptor which only yields records passing all of its
+/// [`Filter`]s.
+///
+/// Filters are applied in the order they were added and a dropped
+/// record is attributed to the first filter it fails.
+///
+/// ```
+/// use fxread::{initialize_reader, Filter, FilteredReader};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let mut filtered = FilteredReader::new(reader)
+///     .with(Filter::MinLength(80))
+///     .with(Filter::MinMeanQuality(20.0));
+/// let kept = filtered.by_ref().count();
+///
+/// assert_eq!(filtered.total(), 10);
+/// assert_eq!(kept as u64, filtered.passed());
+/// for (filter, dropped) in filtered.summary() {
+///     println!("{}\t{}", filter, dropped);
+/// }
+/// ```
+pub struct FilteredReader<R: FastxRead> {
+    reader: R,
+    filters: Vec<(Filter, u64)>,
+    passed: u64,
+}
+impl<R: FastxRead> FilteredReader<R> {
+    /// Creates a new [`FilteredReader`] without any filters
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            filters: Vec::new(),
+            passed: 0,
+        }
+    }
+
+    /// Adds a [`Filter`] which records must satisfy
+    #[must_use]
+    pub fn with(mut self, filter: Filter) -> Self {
+        self.filters.push((filter, 0));
+        self
+    }
+
+    /// Returns each filter with the number of records it dropped
+    pub fn summary(&self) -> impl Iterator<Item = (&Filter, u64)> {
+        self.filters
+            .iter()
+            .map(|(filter, dropped)| (filter, *dropped))
+    }
+
+    /// Returns the number of records which passed all filters
+    #[must_use]
+    pub fn passed(&self) -> u64 {
+        self.passed
+    }
+
+    /// Returns the number of records which were dropped
+    #[must_use]
+    pub fn dropped(&self) -> u64 {
+        self.filters.iter().map(|(_, dropped)| dropped).sum()
+    }
+
+    /// Returns the number of records which were read
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.passed + self.dropped()
+    }
+}
+
+impl<R: FastxRead> FastxRead for FilteredReader<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        while let Some(record) = self.reader.next_record()? {
+            match self
+                .filters
+                .iter_mut()
+                .find(|(filter, _)| !filter.keep(&record))
+            {
+                Some((_, dropped)) => *dropped += 1,
+                None => {
+                    self.passed += 1;
+                    return Ok(Some(record));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for FilteredReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dust_score, error_probability, Filter, FilteredReader};
+    use crate::{FastqReader, Record};
+    #[cfg(feature = "regex")]
+    use regex::bytes::Regex;
+
+    fn fastq(seq: &[u8], qual: &[u8]) -> Record {
+        Record::new_fastq_from_parts(b"seq.0", seq, qual).unwrap()
+    }
+
+    #[test]
+    fn lengths() {
+        let record = fastq(b"ACGTA", b"IIIII");
+        assert!(Filter::MinLength(5).keep(&record));
+        assert!(!Filter::MinLength(6).keep(&record));
+        assert!(Filter::MaxLength(5).keep(&record));
+        assert!(!Filter::MaxLength(4).keep(&record));
+    }
+
+    #[test]
+    fn mean_quality() {
+        // '5' = 20 and '?' = 30
+        let record = fastq(b"ACGT", b"55??");
+        assert!(Filter::MinMeanQuality(25.0).keep(&record));
+        assert!(!Filter::MinMeanQuality(25.1).keep(&record));
+    }
+
+    #[test]
+    fn expected_errors() {
+        assert!((error_probability(b'+') - 0.1).abs() < 1e-12);
+        assert!((error_probability(b'5') - 0.01).abs() < 1e-12);
+        assert_eq!(error_probability(b'!'), 1.0);
+        let record = fastq(b"ACGT", b"++55");
+        assert!(Filter::MaxExpectedErrors(0.23).keep(&record));
+        assert!(!Filter::MaxExpectedErrors(0.21).keep(&record));
+    }
+
+    #[test]
+    fn quality_filters_keep_fasta() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ACGT").unwrap();
+        assert!(Filter::MinMeanQuality(40.0).keep(&record));
+        assert!(Filter::MaxExpectedErrors(0.0).keep(&record));
+    }
+
+    #[test]
+    fn n_fraction() {
+        let record = fastq(b"ANNnACGTAC", b"IIIIIIIIII");
+        assert!(Filter::MaxNFraction(0.3).keep(&record));
+        assert!(!Filter::MaxNFraction(0.29).keep(&record));
+    }
+
+    #[test]
+    fn complexity() {
+        assert!(dust_score(b"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAA") > 10.0);
+        assert!(dust_score(b"ACACACACACACACACACACACACACACAC") > 5.0);
+        assert!(dust_score(b"ACGTTGCAAGCTTCGATCGGATCCTAGCAT") < 1.0);
+        assert_eq!(dust_score(b"AAA"), 0.0);
+        let record = fastq(b"AAAAAAAAAAAAAAAAAAAA", b"IIIIIIIIIIIIIIIIIIII");
+        assert!(!Filter::LowComplexity(7.0).keep(&record));
+    }
+
+    #[test]
+    fn complexity_independent_of_length() {
+        // A linear congruential generator for reproducible random bases
+        let mut state = 42u64;
+        let seq: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                b"ACGT"[(state >> 62) as usize]
+            })
+            .collect();
+        for length in [150, 1_000, 10_000] {
+            let record = Record::new_fasta_from_parts(b"seq.0", &seq[..length]).unwrap();
+            assert!(dust_score(record.seq()) < 2.0);
+            assert!(Filter::LowComplexity(7.0).keep(&record));
+        }
+        let mut repeat = seq.clone();
+        repeat[5_000..5_040].fill(b'A');
+        let record = Record::new_fasta_from_parts(b"seq.0", &repeat).unwrap();
+        assert!(!Filter::LowComplexity(7.0).keep(&record));
+        let record = Record::new_fasta_from_parts(b"seq.0", &[b'A'; 10_000]).unwrap();
+        assert!((dust_score(record.seq()) - 31.0).abs() < 1e-9);
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn header_regex() {
+        let record = Record::new_fasta_from_parts(b"read_12 sample=A", b"ACGT").unwrap();
+        let filter = Filter::HeaderRegex(Regex::new(r"sample=A\b").unwrap());
+        assert!(filter.keep(&record));
+        let filter = Filter::HeaderRegex(Regex::new(r"^read_1\b").unwrap());
+        assert!(!filter.keep(&record));
+        assert_eq!(filter.to_string(), r"header_regex(^read_1\b)");
+    }
+
+    #[test]
+    fn per_filter_counts() {
+        let fastq: &'static [u8] = b"@a\nAC\n+\nII\n@b\nACGTN\n+\nIIIII\n@c\nNNNNN\n+\nIIIII\n@d\nACGTA\n+\n!!!!!\n@e\nACGTA\n+\nIIIII\n";
+        let reader = FastqReader::new(fastq);
+        let mut filtered = FilteredReader::new(reader)
+            .with(Filter::MinLength(3))
+            .with(Filter::MaxNFraction(0.5))
+            .with(Filter::MinMeanQuality(20.0));
+        let kept: Vec<_> = filtered.by_ref().map(|r| r.id_str().to_string()).collect();
+        assert_eq!(kept, ["b", "e"]);
+        let summary: Vec<_> = filtered
+            .summary()
+            .map(|(f, n)| (f.to_string(), n))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("min_length(3)".to_string(), 1),
+                ("max_n_fraction(0.5)".to_string(), 1),
+                ("min_mean_quality(20)".to_string(), 1)
+            ]
+        );
+        assert_eq!(filtered.passed(), 2);
+        assert_eq!(filtered.dropped(), 3);
+        assert_eq!(filtered.total(), 5);
+    }
+
+    #[test]
+    fn compose_filters() {
+        let fastq: &'static [u8] = b"@a\nAC\n+\nII\n@b\nACGT\n+\nIIII\n";
+        let inner = FilteredReader::new(FastqReader::new(fastq)).with(Filter::MinLength(3));
+        let outer = FilteredReader::new(inner).with(Filter::MaxLength(3));
+        assert_eq!(outer.count(), 0);
+    }
+}
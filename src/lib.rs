@@ -26,6 +26,12 @@
 //!     .for_each(|record| println!("{:?}", record));
 //! ```
 
+/// Module for sequence alphabets
+pub mod alphabet;
+
+/// Module for counting records without parsing them
+pub mod count;
+
 /// Module for fastx records.
 pub mod record;
 
@@ -38,12 +44,107 @@ pub mod fastq;
 /// Module for a fasta reader
 pub mod fasta;
 
+/// Module for line scanning shared by the readers
+mod parse;
+
+/// Module for a memory mapped reader
+pub mod mmap;
+
+/// Module for a fasta and fastq writer
+pub mod writer;
+
+/// Module for sampling records
+pub mod sample;
+
+/// Module for sorting records which may not fit in memory
+pub mod sort;
+
+/// Module for splitting records into multiple files
+pub mod split;
+
+/// Module for summary statistics of records
+pub mod stats;
+
+/// Module for removing duplicate records
+pub mod dedup;
+
+/// Module for demultiplexing reads by barcode
+pub mod demux;
+
+/// Module for extracting records by ID
+pub mod extract;
+
+/// Module for searching record headers and sequences
+pub mod search;
+
+/// Module for filtering records
+pub mod filter;
+
+/// Module for translating nucleotide records into proteins
+pub mod translate;
+
+/// Module for extracting UMIs and cell barcodes into headers
+pub mod umi;
+
+/// Module for building readers with explicit settings
+pub mod builder;
+
+/// Module for reading multiple files as one stream
+pub mod multi;
+
+/// Module for tracking the progress of a reader
+pub mod progress;
+
+/// Module for quality control profiles of records
+pub mod qc;
+
 /// Module for utility functions associated with creating
 /// the correct fastx reader.
 pub mod utils;
 
+/// Module for parallel iteration over records
+#[cfg(feature = "rayon")]
+pub mod par;
+
+/// Module for asynchronous fasta and fastq readers
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+
+/// Module for helpers shared by the tests
+#[cfg(test)]
+mod test_utils;
+
+pub use alphabet::Alphabet;
+#[cfg(feature = "tokio")]
+pub use async_reader::{
+    initialize_async_reader, AsyncFastaReader, AsyncFastqReader, AsyncFastxReader,
+};
+pub use builder::{Compression, ReaderBuilder};
+pub use count::{count_reader, count_records, count_records_with, Counts};
+pub use dedup::{DedupKey, DedupReader, PairedDedupReader};
+pub use demux::{Assignment, BarcodeLocation, BarcodeSheet, Demultiplexer};
+pub use extract::ExtractReader;
 pub use fasta::FastaReader;
-pub use fastq::FastqReader;
-pub use fastx::FastxRead;
-pub use record::Record;
-pub use utils::{initialize_reader, initialize_stdin_reader};
+pub use fastq::{FastqReader, Strictness};
+pub use fastx::{EmptyReader, FastxRead, Format};
+pub use filter::{Filter, FilteredReader};
+pub use mmap::{MmapFile, MmapReader, RefRecords};
+pub use multi::MultiReader;
+#[cfg(feature = "rayon")]
+pub use par::{ParRecords, ParallelFastxRead, TryParRecords};
+pub use progress::{Progress, ProgressReader};
+pub use qc::{CycleComposition, CycleQuality, Overrepresented, Qc, QcReport};
+pub use record::{Record, RefRecord};
+pub use sample::{
+    paired_reservoir_sample, reservoir_sample, FractionSampler, PairedFractionSampler,
+};
+pub use search::{HeaderPattern, Hit, Match, Motif, SearchReader, Strand};
+pub use sort::{ExternalSorter, SortKey};
+pub use split::{SplitMode, Splitter};
+pub use stats::{QualityEncoding, Stats};
+pub use translate::{Frame, GeneticCode, Orf, StopCodon, Translator};
+pub use umi::{
+    AnnotateMates, Barcodes, HeaderFormat, PairedUmiReader, UmiExtractor, UmiPattern, UmiReader,
+};
+pub use utils::{initialize_mmap_reader, initialize_reader, initialize_stdin_reader};
+pub use writer::{FastxWriter, LineEnding};
@@ -0,0 +1,551 @@
+use super::fastq::Strictness;
+use super::fastx::{FastxRead, Format};
+use super::parse::{check_fasta, check_fastq, line_end, locate_lines, preamble_len};
+use super::record::{Record, RefRecord};
+use anyhow::{anyhow, Result};
+use memchr::memchr;
+use memmap2::Mmap;
+use std::{fs::File, path::Path};
+
+/// Locates the record starting at `pos` and returns the position
+/// following its lines along with the record.
+///
+/// The lines are checked by the same functions as
+/// [`FastaBytes`](crate::fasta::FastaBytes) and
+/// [`FastqBytes`](crate::fastq::FastqBytes) at the given [`Strictness`],
+/// where `number` is the 1-based position of the record.
+fn ref_record(
+    data: &[u8],
+    pos: usize,
+    is_fasta: bool,
+    strictness: Strictness,
+    number: usize,
+) -> (usize, Result<Option<RefRecord<'_>>>) {
+    let rest = &data[pos..];
+    let mut lines = [0..0, 0..0, 0..0, 0..0];
+    let num_lines = if is_fasta { 2 } else { 4 };
+    let (num_lines, used) = locate_lines(rest, &mut lines[..num_lines]);
+    let contents = lines.clone().map(|line| &rest[line]);
+    let at_end = || used == rest.len();
+    let valid = if is_fasta {
+        check_fasta(&contents[..num_lines], at_end)
+    } else {
+        check_fastq(&contents[..num_lines], strictness, number, at_end)
+    };
+    match valid {
+        Ok(true) => {}
+        Ok(false) => return (pos + used, Ok(None)),
+        Err(why) => return (pos + used, Err(why)),
+    }
+    let [id, seq, plus, qual] = lines;
+    let id = id.start + 1..id.end;
+    let record = if is_fasta {
+        RefRecord::new_fasta(&rest[..used], id, seq)
+    } else {
+        RefRecord::new_fastq(&rest[..used], id, seq, plus, qual)
+    };
+    (pos + used, Ok(Some(record)))
+}
+
+/// Returns the start of the first record at or after `from`.
+///
+/// A fastq record start is only accepted if its third line begins
+/// with '+' to avoid splitting on quality lines beginning with '@'.
+fn next_record_start(data: &[u8], from: usize, is_fasta: bool) -> usize {
+    if from == 0 {
+        return 0;
+    }
+    let marker = if is_fasta { b'>' } else { b'@' };
+    let mut idx = from - 1;
+    while idx < data.len() {
+        let newline = match memchr(b'\n', &data[idx..]) {
+            Some(offset) => idx + offset,
+            None => return data.len(),
+        };
+        let start = newline + 1;
+        if start < data.len() && data[start] == marker {
+            if is_fasta {
+                return start;
+            }
+            let seq = line_end(data, start) + 1;
+            let plus = line_end(data, seq.min(data.len())) + 1;
+            if plus < data.len() && data[plus] == b'+' {
+                return start;
+            }
+        }
+        idx = start;
+    }
+    data.len()
+}
+
+/// A memory mapped fasta or fastq file.
+///
+/// Records are borrowed directly from the mapping without copying.
+///
+/// Mapping a file is `unsafe`: if the file is truncated or modified by
+/// this or another process while it is mapped, reading the mapping is
+/// undefined behavior (e.g. the process may receive `SIGBUS`). Readers
+/// built by [`initialize_reader`](crate::initialize_reader) use buffered
+/// I/O unless mapping is requested with [`ReaderBuilder::mmap`](crate::ReaderBuilder::mmap).
+pub struct MmapFile {
+    mmap: Mmap,
+    /// The start of the first record after any preamble
+    start: usize,
+    is_fasta: bool,
+}
+impl MmapFile {
+    /// # Usage
+    /// Maps a plaintext fasta or fastq file into memory and determines
+    /// its format from the first character.
+    ///
+    /// A leading byte order mark, blank lines, and comment lines
+    /// (starting with '#' or ';') before the first record are skipped.
+    ///
+    /// ```
+    /// // SAFETY: the example file is not modified while it is mapped
+    /// let file = unsafe { fxread::MmapFile::open("example/sequences.fq") }.unwrap();
+    /// let record = file.records().next().unwrap().unwrap();
+    /// assert_eq!(record.id(), b"seq.0");
+    /// ```
+    ///
+    /// # Safety
+    /// The file must not be truncated or modified while it is mapped.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_file(&File::open(path)?)
+    }
+
+    /// Maps an already opened plaintext file into memory.
+    ///
+    /// # Safety
+    /// The file must not be truncated or modified while it is mapped.
+    pub unsafe fn from_file(file: &File) -> Result<Self> {
+        Self::map(file, None)?.ok_or(anyhow!("No data in input file"))
+    }
+
+    /// Maps a plaintext file into memory using the provided [`Format`]
+    /// or determining it from the first record if none is provided.
+    ///
+    /// Returns `None` if the file does not contain any records.
+    ///
+    /// # Safety
+    /// The file must not be truncated or modified while it is mapped.
+    pub(crate) unsafe fn map(file: &File, format: Option<Format>) -> Result<Option<Self>> {
+        if file.metadata()?.len() == 0 {
+            return Ok(None);
+        }
+        // SAFETY: the mapping is read only and the caller guarantees the
+        // file is not modified while it is mapped.
+        let mmap = unsafe { Mmap::map(file)? };
+        let start = preamble_len(&mmap);
+        let format = match (format, mmap.get(start)) {
+            (_, None) => return Ok(None),
+            (Some(format), _) => format,
+            (None, Some(marker)) => match Format::from_marker(*marker) {
+                Some(format) => format,
+                None => return Err(anyhow!("Unrecognized file format")),
+            },
+        };
+        Ok(Some(Self {
+            mmap,
+            start,
+            is_fasta: format == Format::Fasta,
+        }))
+    }
+
+    /// Checks if the mapped file is fasta formatted
+    #[must_use]
+    pub fn is_fasta(&self) -> bool {
+        self.is_fasta
+    }
+
+    /// Returns the [`Format`] of the mapped file
+    #[must_use]
+    pub fn format(&self) -> Format {
+        if self.is_fasta {
+            Format::Fasta
+        } else {
+            Format::Fastq
+        }
+    }
+
+    /// Returns the raw mapped bytes
+    #[must_use]
+    pub fn data(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// Returns an iterator over the borrowed records of the file
+    #[must_use]
+    pub fn records(&self) -> RefRecords<'_> {
+        RefRecords::new(self.record_data(), self.is_fasta)
+    }
+
+    /// Returns the mapped bytes starting at the first record
+    pub(crate) fn record_data(&self) -> &[u8] {
+        &self.mmap[self.start..]
+    }
+
+    /// # Usage
+    /// Splits the file into at most `n` record-aligned chunks which can
+    /// be parsed independently (e.g. on separate threads).
+    ///
+    /// ```
+    /// // SAFETY: the example file is not modified while it is mapped
+    /// let file = unsafe { fxread::MmapFile::open("example/sequences.fq") }.unwrap();
+    /// let total: usize = file
+    ///     .chunks(4)
+    ///     .into_iter()
+    ///     .map(|chunk| chunk.count())
+    ///     .sum();
+    /// assert_eq!(total, 10);
+    /// ```
+    #[must_use]
+    pub fn chunks(&self, n: usize) -> Vec<RefRecords<'_>> {
+        let data = self.record_data();
+        let n = n.max(1);
+        let mut chunks = Vec::with_capacity(n);
+        let mut start = 0;
+        for idx in 1..=n {
+            let end = if idx == n {
+                data.len()
+            } else {
+                next_record_start(data, idx * data.len() / n, self.is_fasta).max(start)
+            };
+            if end > start {
+                chunks.push(RefRecords::new(&data[start..end], self.is_fasta));
+            }
+            start = end;
+        }
+        chunks
+    }
+}
+
+/// An iterator over the [`RefRecord`]s of a buffer.
+///
+/// Records are split on their line layout like a lenient
+/// [`FastqReader`](crate::FastqReader).
+pub struct RefRecords<'a> {
+    data: &'a [u8],
+    pos: usize,
+    is_fasta: bool,
+}
+impl<'a> RefRecords<'a> {
+    fn new(data: &'a [u8], is_fasta: bool) -> Self {
+        Self {
+            data,
+            pos: 0,
+            is_fasta,
+        }
+    }
+}
+
+impl<'a> Iterator for RefRecords<'a> {
+    type Item = Result<RefRecord<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (next, record) = ref_record(self.data, self.pos, self.is_fasta, Strictness::Lenient, 0);
+        match record {
+            Ok(Some(record)) => {
+                self.pos = next;
+                Some(Ok(record))
+            }
+            Ok(None) => None,
+            Err(why) => {
+                self.pos = self.data.len();
+                Some(Err(why))
+            }
+        }
+    }
+}
+
+/// A reader over a memory mapped file which yields owned [`Record`]s.
+pub struct MmapReader {
+    file: MmapFile,
+    pos: usize,
+    strictness: Strictness,
+    /// The number of records parsed so far
+    count: usize,
+}
+impl MmapReader {
+    /// # Example
+    /// Creates a new [`MmapReader`] from a plaintext file path.
+    ///
+    /// ```
+    /// // SAFETY: the example file is not modified while it is mapped
+    /// let reader = unsafe { fxread::MmapReader::open("example/sequences.fa") }.unwrap();
+    /// assert_eq!(reader.count(), 10);
+    /// ```
+    ///
+    /// # Safety
+    /// The file must not be truncated or modified while it is mapped.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(MmapFile::open(path)?))
+    }
+
+    /// Creates a new [`MmapReader`] from an [`MmapFile`]
+    #[must_use]
+    pub fn new(file: MmapFile) -> Self {
+        Self::with_strictness(file, Strictness::default())
+    }
+
+    /// # Example
+    /// Creates a new [`MmapReader`] which validates each fastq record
+    /// at the provided [`Strictness`].
+    ///
+    /// ```
+    /// use fxread::{FastxRead, MmapFile, MmapReader, Strictness};
+    ///
+    /// // SAFETY: the example file is not modified while it is mapped
+    /// let file = unsafe { MmapFile::open("example/sequences.fq") }.unwrap();
+    /// let reader = MmapReader::with_strictness(file, Strictness::Strict);
+    /// assert_eq!(reader.count(), 10);
+    /// ```
+    #[must_use]
+    pub fn with_strictness(file: MmapFile, strictness: Strictness) -> Self {
+        let pos = file.start;
+        Self {
+            file,
+            pos,
+            strictness,
+            count: 0,
+        }
+    }
+}
+
+impl FastxRead for MmapReader {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        let number = self.count + 1;
+        let data = self.file.data();
+        let (next, record) =
+            ref_record(data, self.pos, self.file.is_fasta, self.strictness, number);
+        if next > self.pos {
+            self.count = number;
+        }
+        self.pos = next;
+        Ok(record?.map(|record| record.to_record()))
+    }
+
+    fn format(&self) -> Option<Format> {
+        Some(self.file.format())
+    }
+}
+
+impl Iterator for MmapReader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MmapFile, MmapReader};
+    use crate::test_utils::write_temp;
+    use crate::{FastxRead, Strictness};
+    use std::path::Path;
+
+    fn open(path: impl AsRef<Path>) -> anyhow::Result<MmapFile> {
+        // SAFETY: test files are not modified while they are mapped
+        unsafe { MmapFile::open(path) }
+    }
+
+    #[test]
+    fn read_fasta() {
+        let file = open("example/sequences.fa").unwrap();
+        assert!(file.is_fasta());
+        let records: Vec<_> = file.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[0].id(), b"seq.0");
+        assert_eq!(
+            records[0].seq(),
+            b"TAGTGCTTTCGATGGAACTGGACCGAGAATTCTATCGCAAATGGAACCGGAGTGACGGTGTTTCTAGACGCTCCTCACAA"
+        );
+        assert!(records[0].qual().is_none());
+    }
+
+    #[test]
+    fn read_fastq() {
+        let file = open("example/sequences.fq").unwrap();
+        assert!(!file.is_fasta());
+        let records: Vec<_> = file.records().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 10);
+        assert_eq!(records[9].id(), b"seq.9");
+        assert_eq!(records[9].plus().unwrap(), b"+");
+        assert_eq!(records[9].qual().unwrap().len(), records[9].seq().len());
+    }
+
+    #[test]
+    fn owned_records_match() {
+        let owned: Vec<_> = crate::FastqReader::new(std::io::BufReader::new(
+            std::fs::File::open("example/sequences.fq").unwrap(),
+        ))
+        .collect();
+        let mapped: Vec<_> = MmapReader::new(open("example/sequences.fq").unwrap()).collect();
+        assert_eq!(owned.len(), mapped.len());
+        for (a, b) in owned.iter().zip(mapped.iter()) {
+            assert_eq!(a.data(), b.data());
+            assert_eq!(a.qual(), b.qual());
+        }
+    }
+
+    #[test]
+    fn missing_final_newline() {
+        let path = write_temp("no-newline.fq", b"@seq.0\nACGT\n+\n1234");
+        let records: Vec<_> = MmapReader::new(open(&path).unwrap()).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].qual().unwrap(), b"1234");
+        assert_eq!(records[0].data(), b"@seq.0\nACGT\n+\n1234\n");
+    }
+
+    #[test]
+    fn chunks_cover_all_records() {
+        let file = open("example/sequences.fa").unwrap();
+        for n in 1..=20 {
+            let ids: Vec<_> = file
+                .chunks(n)
+                .into_iter()
+                .flat_map(|chunk| chunk.map(|r| r.unwrap().id().to_vec()))
+                .collect();
+            let expected: Vec<_> = file.records().map(|r| r.unwrap().id().to_vec()).collect();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[test]
+    fn chunks_skip_quality_markers() {
+        let fastq = (0..50)
+            .flat_map(|i| format!("@seq.{}\nACGT\n+\n@@@@\n", i).into_bytes())
+            .collect::<Vec<u8>>();
+        let path = write_temp("quality-markers.fq", &fastq);
+        let file = open(&path).unwrap();
+        for n in 1..=32 {
+            let records: Vec<_> = file
+                .chunks(n)
+                .into_iter()
+                .flat_map(|chunk| chunk.map(|r| r.unwrap().to_record()))
+                .collect();
+            assert_eq!(records.len(), 50);
+            assert!(records.iter().all(|r| r.seq() == b"ACGT"));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn markers_in_headers() {
+        let path = write_temp("header-markers.fa", b">seq>0\nACGT\n>seq>1\nTTTT\n");
+        let file = open(&path).unwrap();
+        let ids: Vec<_> = file
+            .chunks(8)
+            .into_iter()
+            .flat_map(|chunk| chunk.map(|r| r.unwrap().id().to_vec()))
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(ids, vec![b"seq>0".to_vec(), b"seq>1".to_vec()]);
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let path = write_temp("crlf.fq", b"@seq.0\r\nACGT\r\n+\r\n1234\r\n");
+        let file = open(&path).unwrap();
+        let record = file.records().next().unwrap().unwrap();
+        assert_eq!(record.id(), b"seq.0");
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(record.plus().unwrap(), b"+");
+        assert_eq!(record.qual().unwrap(), b"1234");
+        assert_eq!(record.to_record().data(), b"@seq.0\nACGT\n+\n1234\n");
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn misplaced_marker() {
+        let path = write_temp("misplaced.fa", b">seq.0\nACGT\nseq.1\n>ACGT\n");
+        let file = open(&path).unwrap();
+        let records: Vec<_> = file.records().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records[1].is_err());
+    }
+
+    #[test]
+    fn empty_file() {
+        let path = write_temp("empty.fa", b"");
+        let file = open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.is_err());
+    }
+
+    #[test]
+    fn skip_preamble() {
+        let path = write_temp(
+            "preamble.fa",
+            b"\xEF\xBB\xBF# header\n\n>seq.0\nACGT\n>seq.1\nTTTT\n",
+        );
+        let file = open(&path).unwrap();
+        assert!(file.is_fasta());
+        assert_eq!(file.records().count(), 2);
+        assert_eq!(
+            file.chunks(2).into_iter().map(|c| c.count()).sum::<usize>(),
+            2
+        );
+        let records: Vec<_> = MmapReader::new(file).collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(records[0].id(), b"seq.0");
+    }
+
+    #[test]
+    fn only_preamble() {
+        let path = write_temp("only-preamble.fa", b"# header\n\n");
+        let file = open(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(file.is_err());
+    }
+
+    #[test]
+    fn strictness() {
+        let path = write_temp(
+            "strict.fq",
+            b"@seq.0\r\nACGT\r\n+seq.0\r\n1234\r\n@seq.1\nAC\n+\n1\n",
+        );
+        let mut reader = MmapReader::with_strictness(open(&path).unwrap(), Strictness::Standard);
+        assert_eq!(reader.next_record().unwrap().unwrap().id(), b"seq.0");
+        let why = reader.next_record().unwrap_err();
+        assert_eq!(
+            why.to_string(),
+            "Record 2: sequence length (2) does not match quality length (1)"
+        );
+        assert_eq!(MmapReader::new(open(&path).unwrap()).count(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn matches_buffered_reader() {
+        let inputs: [&[u8]; 4] = [
+            b"@seq.0\nACGT\n+\n1234\n\n\n",
+            b"@seq.0\nACGT\n+\n1234\n\n@seq.1\nA\n+\n1\n",
+            b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC\n",
+            b"@seq.0\r\nACGT\r\n+\r\n1234",
+        ];
+        for (idx, input) in inputs.into_iter().enumerate() {
+            let path = write_temp(&format!("matches-{}.fq", idx), input);
+            for strictness in [Strictness::Lenient, Strictness::Standard] {
+                let mut buffered = crate::FastqReader::with_strictness(input, strictness);
+                let mut mapped = MmapReader::with_strictness(open(&path).unwrap(), strictness);
+                for _ in 0..3 {
+                    let expected = buffered.next_record().map_err(|e| e.to_string());
+                    let found = mapped.next_record().map_err(|e| e.to_string());
+                    assert_eq!(
+                        expected.map(|r| r.map(|r| r.data().to_vec())),
+                        found.map(|r| r.map(|r| r.data().to_vec())),
+                    );
+                }
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
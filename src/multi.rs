@@ -0,0 +1,250 @@
+use super::fastx::{EmptyReader, FastxRead, Format};
+use super::{ReaderBuilder, Record};
+use anyhow::{anyhow, bail, Result};
+use std::path::{Path, PathBuf};
+
+/// A reader which iterates over the records of multiple files in order.
+///
+/// Each file is opened up front with its own detected compression and
+/// all files must share the same format. Files without any records
+/// (when allowed by the [`ReaderBuilder`]) do not take part in the
+/// format validation.
+///
+/// ```
+/// use fxread::MultiReader;
+///
+/// let paths = ["example/sequences.fq", "example/sequences.fq.gz"];
+/// let mut reader = MultiReader::new(paths).unwrap();
+/// reader.next().unwrap();
+/// assert_eq!(reader.current_index(), Some(0));
+/// assert_eq!(reader.count(), 19);
+/// ```
+pub struct MultiReader {
+    paths: Vec<PathBuf>,
+    readers: Vec<Box<dyn FastxRead<Item = Record> + Send>>,
+    format: Option<Format>,
+    /// The index of the file currently being read
+    idx: usize,
+    /// The index of the file of the most recent record
+    current: Option<usize>,
+}
+impl MultiReader {
+    /// # Usage
+    /// Opens every path with [`initialize_reader`](crate::initialize_reader)
+    /// and validates that they share a format.
+    ///
+    /// ```
+    /// use fxread::MultiReader;
+    ///
+    /// let paths = ["example/sequences.fa", "example/sequences.fq"];
+    /// assert!(MultiReader::new(paths).is_err());
+    /// ```
+    pub fn new<I, P>(paths: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Self::with_builder(paths, ReaderBuilder::new())
+    }
+
+    /// # Usage
+    /// Opens every path with the provided [`ReaderBuilder`] and
+    /// validates that they share a format.
+    ///
+    /// ```
+    /// use fxread::{Format, MultiReader, ReaderBuilder};
+    ///
+    /// let builder = ReaderBuilder::new().format(Format::Fasta);
+    /// let paths = ["example/sequences.fa", "example/sequences.fa.zst"];
+    /// let reader = MultiReader::with_builder(paths, builder).unwrap();
+    /// assert_eq!(reader.count(), 20);
+    /// ```
+    pub fn with_builder<I, P>(paths: I, builder: ReaderBuilder) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let paths: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect();
+        if paths.is_empty() {
+            bail!("No input files provided");
+        }
+        let mut readers = Vec::with_capacity(paths.len());
+        let mut format: Option<(Format, usize)> = None;
+        for (idx, path) in paths.iter().enumerate() {
+            let reader = builder
+                .open(path)
+                .map_err(|why| anyhow!("{}: {}", path.display(), why))?;
+            match (format, reader.format()) {
+                (None, Some(found)) => format = Some((found, idx)),
+                (Some((expected, first)), Some(found)) if expected != found => bail!(
+                    "Input format mismatch: {} is {:?} but {} is {:?}",
+                    path.display(),
+                    found,
+                    paths[first].display(),
+                    expected
+                ),
+                _ => {}
+            }
+            readers.push(reader);
+        }
+        Ok(Self {
+            paths,
+            readers,
+            format: format.map(|(format, _)| format),
+            idx: 0,
+            current: None,
+        })
+    }
+
+    /// Returns the paths of all files in the order they are read
+    #[must_use]
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Returns the index of the file the most recent record came from
+    #[must_use]
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Returns the path of the file the most recent record came from
+    #[must_use]
+    pub fn current_path(&self) -> Option<&Path> {
+        self.current.map(|idx| self.paths[idx].as_path())
+    }
+}
+
+impl FastxRead for MultiReader {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        while self.idx < self.readers.len() {
+            let record = self.readers[self.idx]
+                .next_record()
+                .map_err(|why| anyhow!("{}: {}", self.paths[self.idx].display(), why))?;
+            if let Some(record) = record {
+                self.current = Some(self.idx);
+                return Ok(Some(record));
+            }
+            // release the exhausted file
+            self.readers[self.idx] = Box::new(EmptyReader);
+            self.idx += 1;
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.format
+    }
+}
+
+impl Iterator for MultiReader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiReader;
+    use crate::test_utils::write_temp;
+    use crate::{FastxRead, Format, ReaderBuilder};
+    use std::path::Path;
+
+    #[test]
+    fn read_lanes_in_order() {
+        let lanes = [
+            write_temp("L001.fq", b"@a.0\nACGT\n+\n1234\n@a.1\nACGT\n+\n1234\n"),
+            write_temp("L002.fq", b"@b.0\nACGT\n+\n1234\n"),
+        ];
+        let mut reader = MultiReader::new(&lanes).unwrap();
+        assert_eq!(reader.format(), Some(Format::Fastq));
+        assert!(reader.current_path().is_none());
+
+        let mut seen = Vec::new();
+        while let Some(record) = reader.next_record().unwrap() {
+            seen.push((record.id_str().to_string(), reader.current_index().unwrap()));
+        }
+        assert_eq!(reader.current_path(), Some(lanes[1].as_path()));
+        lanes.iter().for_each(|p| std::fs::remove_file(p).unwrap());
+        assert_eq!(
+            seen,
+            [
+                ("a.0".to_string(), 0),
+                ("a.1".to_string(), 0),
+                ("b.0".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn mixed_compression() {
+        let paths = [
+            "example/sequences.fa.gz",
+            "example/sequences.fa.bz2",
+            "example/sequences.fa.xz",
+            "example/sequences.fa",
+        ];
+        let reader = MultiReader::new(paths).unwrap();
+        assert_eq!(reader.paths().len(), 4);
+        assert_eq!(reader.count(), 40);
+    }
+
+    #[test]
+    fn format_mismatch() {
+        let paths = ["example/sequences.fq", "example/sequences.fa.gz"];
+        let err = MultiReader::new(paths).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Input format mismatch: example/sequences.fa.gz is Fasta but example/sequences.fq is Fastq"
+        );
+    }
+
+    #[test]
+    fn no_paths() {
+        assert!(MultiReader::new(Vec::<&Path>::new()).is_err());
+    }
+
+    #[test]
+    fn missing_path() {
+        let paths = ["example/sequences.fq", "example/missing.fq"];
+        let err = MultiReader::new(paths).err().unwrap();
+        assert!(err.to_string().starts_with("example/missing.fq: "));
+    }
+
+    #[test]
+    fn skip_empty_files() {
+        let empty = write_temp("empty.fa", b"");
+        let paths = [
+            empty.as_path(),
+            Path::new("example/sequences.fq"),
+            empty.as_path(),
+        ];
+        assert!(MultiReader::new(paths).is_err());
+
+        let builder = ReaderBuilder::new().allow_empty(true);
+        let mut reader = MultiReader::with_builder(paths, builder).unwrap();
+        assert_eq!(reader.format(), Some(Format::Fastq));
+        reader.next().unwrap();
+        assert_eq!(reader.current_index(), Some(1));
+        assert_eq!(reader.count(), 9);
+        std::fs::remove_file(&empty).unwrap();
+    }
+
+    #[test]
+    fn record_errors_name_file() {
+        let bad = write_temp("bad.fa", b">seq.0\nACGT\nACGT\nACGT\n");
+        let mut reader = MultiReader::new([&bad]).unwrap();
+        reader.next_record().unwrap();
+        let err = reader.next_record().err().unwrap();
+        std::fs::remove_file(&bad).unwrap();
+        assert!(err.to_string().starts_with(&bad.display().to_string()));
+    }
+}
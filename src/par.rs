@@ -0,0 +1,340 @@
+use super::fastx::FastxRead;
+use super::record::Record;
+use anyhow::Result;
+use rayon::iter::plumbing::{Folder, Reducer, UnindexedConsumer};
+use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
+
+/// The default number of records read into each batch.
+const BATCH_SIZE: usize = 1024;
+
+/// Extension trait which turns any [`FastxRead`] into a rayon
+/// [`ParallelIterator`] over its [`Record`]s.
+pub trait ParallelFastxRead: FastxRead<Item = Record> + Send + Sized {
+    /// # Usage
+    /// Creates a [`ParRecords`] parallel iterator from the reader.
+    ///
+    /// ```
+    /// use fxread::{initialize_reader, ParallelFastxRead};
+    /// use rayon::prelude::*;
+    ///
+    /// let reader = initialize_reader("example/sequences.fq").unwrap();
+    /// let num_long = reader
+    ///     .par_bridge_records()
+    ///     .filter(|record| record.seq().len() > 50)
+    ///     .count();
+    /// assert_eq!(num_long, 10);
+    /// ```
+    fn par_bridge_records(self) -> ParRecords<Self> {
+        ParRecords::new(self)
+    }
+
+    /// # Usage
+    /// Creates a [`TryParRecords`] parallel iterator from the reader
+    /// which yields read errors instead of panicking on them.
+    ///
+    /// ```
+    /// use fxread::{FastqReader, ParallelFastxRead, Record};
+    /// use rayon::prelude::*;
+    ///
+    /// let input: &'static [u8] = b"@seq.0\nACGT\n+\n1234\nseq.1\nACGT\n+\n1234\n";
+    /// let records: anyhow::Result<Vec<Record>> = FastqReader::new(input)
+    ///     .try_par_bridge_records()
+    ///     .collect();
+    /// assert!(records.is_err());
+    /// ```
+    fn try_par_bridge_records(self) -> TryParRecords<Self> {
+        TryParRecords::new(self)
+    }
+}
+impl<R: FastxRead<Item = Record> + Send> ParallelFastxRead for R {}
+
+/// A [`ParallelIterator`] over the [`Record`]s of a [`FastxRead`].
+///
+/// Records are read sequentially from the underlying reader in batches
+/// and each batch is distributed across the rayon thread pool.
+///
+/// # Panics
+/// Panics if the reader returns an error, see [`TryParRecords`] to
+/// handle it instead.
+pub struct ParRecords<R> {
+    inner: TryParRecords<R>,
+}
+impl<R: FastxRead<Item = Record> + Send> ParRecords<R> {
+    /// Creates a new [`ParRecords`] with the default batch size
+    /// that does not preserve the input order.
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: TryParRecords::new(reader),
+        }
+    }
+
+    /// Sets the number of records read into each batch.
+    ///
+    /// # Panics
+    /// Panics if `batch_size` is zero.
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.inner = self.inner.batch_size(batch_size);
+        self
+    }
+
+    /// Sets whether records are yielded in input order.
+    ///
+    /// When enabled, order-sensitive operations such as `collect`
+    /// see records in the same order as the input. Only one batch
+    /// is processed at a time while the next one is read.
+    ///
+    /// ```
+    /// use fxread::{initialize_reader, ParallelFastxRead};
+    /// use rayon::prelude::*;
+    ///
+    /// let reader = initialize_reader("example/sequences.fa").unwrap();
+    /// let ids: Vec<String> = reader
+    ///     .par_bridge_records()
+    ///     .batch_size(3)
+    ///     .preserve_order(true)
+    ///     .map(|record| record.id_str().to_string())
+    ///     .collect();
+    /// assert_eq!(ids[0], "seq.0");
+    /// assert_eq!(ids[9], "seq.9");
+    /// ```
+    #[must_use]
+    pub fn preserve_order(mut self, preserve_order: bool) -> Self {
+        self.inner = self.inner.preserve_order(preserve_order);
+        self
+    }
+}
+
+impl<R: FastxRead<Item = Record> + Send> ParallelIterator for ParRecords<R> {
+    type Item = Record;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.inner
+            .map(|record| match record {
+                Ok(record) => record,
+                Err(why) => panic!("{}", why),
+            })
+            .drive_unindexed(consumer)
+    }
+}
+
+/// A [`ParallelIterator`] over the [`Record`]s of a [`FastxRead`] which
+/// yields the error of the reader instead of panicking.
+///
+/// Reading stops at the first error, which is yielded after the records
+/// read before it. Collecting into a `Result` returns the error.
+pub struct TryParRecords<R> {
+    reader: R,
+    batch_size: usize,
+    preserve_order: bool,
+    failed: bool,
+}
+impl<R: FastxRead<Item = Record> + Send> TryParRecords<R> {
+    /// Creates a new [`TryParRecords`] with the default batch size
+    /// that does not preserve the input order.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            batch_size: BATCH_SIZE,
+            preserve_order: false,
+            failed: false,
+        }
+    }
+
+    /// Sets the number of records read into each batch.
+    ///
+    /// # Panics
+    /// Panics if `batch_size` is zero.
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "Batch size must be greater than zero");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Sets whether records (and the error) are yielded in input order.
+    #[must_use]
+    pub fn preserve_order(mut self, preserve_order: bool) -> Self {
+        self.preserve_order = preserve_order;
+        self
+    }
+
+    /// Reads up to `batch_size` records, ending the batch (and all
+    /// further reading) at the first error.
+    fn next_batch(&mut self) -> Vec<Result<Record>> {
+        let mut batch = Vec::new();
+        while !self.failed && batch.len() < self.batch_size {
+            match self.reader.next_record() {
+                Ok(Some(record)) => batch.push(Ok(record)),
+                Ok(None) => break,
+                Err(why) => {
+                    self.failed = true;
+                    batch.push(Err(why));
+                }
+            }
+        }
+        batch
+    }
+
+    /// Reads the next batch while the current one is being processed and
+    /// reduces the results from left to right to retain the input order.
+    fn drive_ordered<C>(mut self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Result<Record>>,
+    {
+        let mut result = consumer.split_off_left().into_folder().complete();
+        let mut batch = self.next_batch();
+        while !batch.is_empty() && !consumer.full() {
+            let left = consumer.split_off_left();
+            let (next, batch_result) = rayon::join(
+                || self.next_batch(),
+                || batch.into_par_iter().drive_unindexed(left),
+            );
+            result = consumer.to_reducer().reduce(result, batch_result);
+            batch = next;
+        }
+        let reducer = consumer.to_reducer();
+        reducer.reduce(result, consumer.into_folder().complete())
+    }
+}
+
+impl<R: FastxRead<Item = Record> + Send> ParallelIterator for TryParRecords<R> {
+    type Item = Result<Record>;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        if self.preserve_order {
+            self.drive_ordered(consumer)
+        } else {
+            Batches(self)
+                .par_bridge()
+                .flat_map_iter(|batch| batch)
+                .drive_unindexed(consumer)
+        }
+    }
+}
+
+/// Sequential iterator over the batches of a [`TryParRecords`].
+struct Batches<R>(TryParRecords<R>);
+impl<R: FastxRead<Item = Record> + Send> Iterator for Batches<R> {
+    type Item = Vec<Result<Record>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.0.next_batch();
+        if batch.is_empty() {
+            None
+        } else {
+            Some(batch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParallelFastxRead;
+    use crate::{initialize_reader, FastqReader};
+    use rayon::prelude::*;
+
+    fn gen_fastq(n: usize) -> Vec<u8> {
+        (0..n)
+            .flat_map(|i| format!("@seq.{}\nACGT\n+\nFFFF\n", i).into_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn count_fasta() {
+        let reader = initialize_reader("example/sequences.fa").unwrap();
+        assert_eq!(reader.par_bridge_records().count(), 10);
+    }
+
+    #[test]
+    fn count_gzip_fastq() {
+        let reader = initialize_reader("example/sequences.fq.gz").unwrap();
+        assert_eq!(reader.par_bridge_records().count(), 10);
+    }
+
+    #[test]
+    fn filter_records() {
+        let reader = initialize_reader("example/sequences.fq").unwrap();
+        let num_records = reader
+            .par_bridge_records()
+            .filter(|record| record.id() == b"seq.3")
+            .count();
+        assert_eq!(num_records, 1);
+    }
+
+    #[test]
+    fn small_batches() {
+        let data = gen_fastq(1000);
+        let reader = FastqReader::new(data.as_slice());
+        let num_records = reader.par_bridge_records().batch_size(7).count();
+        assert_eq!(num_records, 1000);
+    }
+
+    #[test]
+    fn preserve_order() {
+        let data = gen_fastq(1000);
+        let reader = FastqReader::new(data.as_slice());
+        let ids: Vec<String> = reader
+            .par_bridge_records()
+            .batch_size(13)
+            .preserve_order(true)
+            .map(|record| record.id_str().to_string())
+            .collect();
+        let expected: Vec<String> = (0..1000).map(|i| format!("seq.{}", i)).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn preserve_order_short_circuit() {
+        let data = gen_fastq(1000);
+        let reader = FastqReader::new(data.as_slice());
+        let first = reader
+            .par_bridge_records()
+            .batch_size(10)
+            .preserve_order(true)
+            .find_first(|record| record.id().ends_with(b"7"));
+        assert_eq!(first.unwrap().id(), b"seq.7");
+    }
+
+    #[test]
+    fn propagate_errors() {
+        let mut data = gen_fastq(100);
+        data.extend_from_slice(b"seq.100\nACGT\n+\nFFFF\n");
+        data.extend(gen_fastq(10));
+        for preserve_order in [false, true] {
+            let records: anyhow::Result<Vec<_>> = FastqReader::new(data.as_slice())
+                .try_par_bridge_records()
+                .batch_size(7)
+                .preserve_order(preserve_order)
+                .collect();
+            assert!(records.is_err());
+
+            let num_ok = FastqReader::new(data.as_slice())
+                .try_par_bridge_records()
+                .batch_size(7)
+                .preserve_order(preserve_order)
+                .filter(Result::is_ok)
+                .count();
+            assert_eq!(num_ok, 100);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn panic_on_error() {
+        let data: &'static [u8] = b"@seq.0\nACGT\n+\nFFFF\nseq.1\nACGT\n+\nFFFF\n";
+        FastqReader::new(data).par_bridge_records().count();
+    }
+
+    #[test]
+    fn empty_reader() {
+        let reader = FastqReader::new(&b""[..]);
+        assert_eq!(reader.par_bridge_records().preserve_order(true).count(), 0);
+    }
+}
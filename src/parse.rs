@@ -0,0 +1,492 @@
+use super::fastq::Strictness;
+use super::record::Record;
+use anyhow::{bail, Result};
+use memchr::{memchr, memchr_iter};
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+/// The UTF-8 byte order mark
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Reads up to `lines.len()` newline terminated lines from `reader` into `bytes`.
+///
+/// Newlines are located with a vectorized search over the buffered data so
+/// a record which is fully buffered is copied with a single extend. The size
+/// of each line (including its terminator) is written into `lines` and the
+/// number of lines read is returned. A final line without a terminator is
+/// treated as terminated and a newline is appended to `bytes`. Windows line
+/// endings are normalized with [`strip_carriage_returns`].
+pub(crate) fn read_lines<B: BufRead>(
+    reader: &mut B,
+    bytes: &mut Vec<u8>,
+    lines: &mut [usize],
+) -> io::Result<usize> {
+    let offset = bytes.len();
+    let mut num_lines = 0;
+    let mut partial = 0;
+    while num_lines < lines.len() {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            Err(why) => return Err(why),
+        };
+        if available.is_empty() {
+            if partial > 0 {
+                bytes.push(b'\n');
+                lines[num_lines] = partial + 1;
+                num_lines += 1;
+            }
+            break;
+        }
+        let mut used = 0;
+        for idx in memchr_iter(b'\n', available) {
+            lines[num_lines] = partial + idx + 1 - used;
+            partial = 0;
+            used = idx + 1;
+            num_lines += 1;
+            if num_lines == lines.len() {
+                break;
+            }
+        }
+        if num_lines < lines.len() {
+            partial += available.len() - used;
+            used = available.len();
+        }
+        bytes.extend_from_slice(&available[..used]);
+        reader.consume(used);
+    }
+    strip_carriage_returns(bytes, offset, &mut lines[..num_lines]);
+    Ok(num_lines)
+}
+
+/// Locates up to `lines.len()` lines at the start of `data` without copying.
+///
+/// This mirrors [`read_lines`] over a borrowed buffer such as a memory
+/// mapped file. The range of each line's content (excluding its "\n" or
+/// "\r\n" terminator) is written into `lines` and a final line without a
+/// terminator extends to the end of `data`. Returns the number of lines
+/// found and the number of bytes they span including their terminators.
+pub(crate) fn locate_lines(data: &[u8], lines: &mut [Range<usize>]) -> (usize, usize) {
+    let mut num_lines = 0;
+    let mut start = 0;
+    while num_lines < lines.len() && start < data.len() {
+        let end = line_end(data, start);
+        let content = if end > start && data[end - 1] == b'\r' {
+            end - 1
+        } else {
+            end
+        };
+        lines[num_lines] = start..content;
+        num_lines += 1;
+        start = (end + 1).min(data.len());
+    }
+    (num_lines, start)
+}
+
+/// Returns the content of each of the `lines` read into `bytes` by
+/// [`read_lines`] excluding its newline
+pub(crate) fn line_contents<'a>(bytes: &'a [u8], lines: &[usize]) -> [&'a [u8]; 4] {
+    let mut contents = [&bytes[..0]; 4];
+    let mut start = 0;
+    for (content, size) in contents.iter_mut().zip(lines) {
+        *content = &bytes[start..start + size - 1];
+        start += size;
+    }
+    contents
+}
+
+/// Checks whether the lines read are only trailing whitespace, which
+/// `at_end` confirms by checking that nothing follows them
+fn trailing_blank<F: FnOnce() -> bool>(lines: &[&[u8]], at_end: F) -> bool {
+    lines
+        .iter()
+        .all(|line| line.iter().all(u8::is_ascii_whitespace))
+        && at_end()
+}
+
+/// Checks the content of the `lines` of a fasta record (excluding their
+/// terminators) as found by [`read_lines`] or [`locate_lines`].
+///
+/// Returns `Ok(false)` at the end of the input: when fewer than two lines
+/// were read (a final header without a sequence is dropped) or only
+/// trailing whitespace remains.
+pub(crate) fn check_fasta<F>(lines: &[&[u8]], at_end: F) -> Result<bool>
+where
+    F: FnOnce() -> bool,
+{
+    if lines.len() < 2 {
+        return Ok(false);
+    }
+    if lines[0].first() != Some(&b'>') {
+        if trailing_blank(lines, at_end) {
+            return Ok(false);
+        }
+        bail!("Misplaced Fasta Marker Sequence '>'");
+    }
+    Ok(true)
+}
+
+/// Checks the content of the `lines` of a fastq record (excluding their
+/// terminators) as found by [`read_lines`] or [`locate_lines`] at the
+/// given [`Strictness`]. The `number` is the 1-based position of the
+/// record in the input.
+///
+/// Returns `Ok(false)` at the end of the input: when no lines were read,
+/// only trailing whitespace remains, or (if lenient) the final record is
+/// truncated.
+pub(crate) fn check_fastq<F>(
+    lines: &[&[u8]],
+    strictness: Strictness,
+    number: usize,
+    at_end: F,
+) -> Result<bool>
+where
+    F: FnOnce() -> bool,
+{
+    if lines.is_empty() {
+        return Ok(false);
+    }
+    if lines[0].first() != Some(&b'@') {
+        if trailing_blank(lines, at_end) {
+            return Ok(false);
+        }
+        bail!("Misplaced Fastq Marker Sequence '@'");
+    }
+    if lines.len() < 4 {
+        if strictness == Strictness::Lenient {
+            return Ok(false);
+        }
+        bail!(
+            "Record {}: truncated record (expected 4 lines but found {})",
+            number,
+            lines.len()
+        );
+    }
+    validate_fastq(lines, strictness, number)?;
+    Ok(true)
+}
+
+/// Validates the four lines of a fastq record at the given [`Strictness`]
+fn validate_fastq(lines: &[&[u8]], strictness: Strictness, number: usize) -> Result<()> {
+    if strictness == Strictness::Lenient {
+        return Ok(());
+    }
+    let [header, seq, plus, qual] = [lines[0], lines[1], lines[2], lines[3]];
+    let id = &header[1..];
+    match plus.first() {
+        Some(b'+') => {}
+        Some(c) => bail!(
+            "Record {}: expected '+' at the start of the third line but found '{}'",
+            number,
+            char::from(*c)
+        ),
+        None => bail!(
+            "Record {}: expected '+' but found an empty third line",
+            number
+        ),
+    }
+    if plus.len() > 1 && plus[1..] != *id {
+        bail!(
+            "Record {}: name after '+' ({}) does not match the header ({})",
+            number,
+            String::from_utf8_lossy(&plus[1..]),
+            String::from_utf8_lossy(id)
+        );
+    }
+    if seq.len() != qual.len() {
+        bail!(
+            "Record {}: sequence length ({}) does not match quality length ({})",
+            number,
+            seq.len(),
+            qual.len()
+        );
+    }
+    if strictness == Strictness::Strict {
+        if let Some(pos) = qual.iter().position(|q| !(b'!'..=b'~').contains(q)) {
+            bail!(
+                "Record {}: invalid quality character {:?} at position {}",
+                number,
+                char::from(qual[pos]),
+                pos + 1
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Assembles a fasta [`Record`] from the `lines` read into `bytes`
+/// following the rules of [`check_fasta`]
+pub(crate) fn fasta_record<F>(bytes: Vec<u8>, lines: &[usize], at_end: F) -> Option<Result<Record>>
+where
+    F: FnOnce() -> bool,
+{
+    let contents = line_contents(&bytes, lines);
+    match check_fasta(&contents[..lines.len()], at_end) {
+        Ok(true) => Some(Ok(Record::new_fasta(bytes, lines[0] - 1, lines[1]))),
+        Ok(false) => None,
+        Err(why) => Some(Err(why)),
+    }
+}
+
+/// Assembles a fastq [`Record`] from the `lines` read into `bytes`
+/// following the rules of [`check_fastq`]
+pub(crate) fn fastq_record<F>(
+    bytes: Vec<u8>,
+    lines: &[usize],
+    strictness: Strictness,
+    number: usize,
+    at_end: F,
+) -> Option<Result<Record>>
+where
+    F: FnOnce() -> bool,
+{
+    let contents = line_contents(&bytes, lines);
+    match check_fastq(&contents[..lines.len()], strictness, number, at_end) {
+        Ok(true) => Some(Ok(Record::new_fastq(
+            bytes,
+            lines[0] - 1,
+            lines[1],
+            lines[2],
+            lines[3],
+        ))),
+        Ok(false) => None,
+        Err(why) => Some(Err(why)),
+    }
+}
+
+/// Removes the '\r' of each "\r\n" terminated line in `bytes[offset..]`
+/// and shrinks the corresponding line sizes.
+///
+/// A '\r' which is not directly followed by '\n' is left untouched.
+pub(crate) fn strip_carriage_returns(bytes: &mut Vec<u8>, offset: usize, lines: &mut [usize]) {
+    if memchr(b'\r', &bytes[offset..]).is_none() {
+        return;
+    }
+    let mut read = offset;
+    let mut write = offset;
+    for size in lines.iter_mut() {
+        let line = read..read + *size;
+        read = line.end;
+        let crlf = bytes[line.clone()].ends_with(b"\r\n");
+        let keep = if crlf { *size - 2 } else { *size };
+        bytes.copy_within(line.start..line.start + keep, write);
+        write += keep;
+        if crlf {
+            bytes[write] = b'\n';
+            write += 1;
+            *size -= 1;
+        }
+    }
+    bytes.truncate(write);
+}
+
+/// Consumes the content before the first record of `reader`.
+///
+/// This is a UTF-8 byte order mark followed by any number of blank
+/// lines and comment lines (starting with '#' or ';').
+pub(crate) fn skip_preamble<B: BufRead>(reader: &mut B) -> io::Result<()> {
+    if reader.fill_buf()?.starts_with(BOM) {
+        reader.consume(BOM.len());
+    }
+    loop {
+        let available = reader.fill_buf()?;
+        match available.first() {
+            Some(b'#' | b';') => {
+                let (used, found) = match memchr(b'\n', available) {
+                    Some(idx) => (idx + 1, true),
+                    None => (available.len(), false),
+                };
+                reader.consume(used);
+                if !found {
+                    skip_line(reader)?;
+                }
+            }
+            Some(b) if b.is_ascii_whitespace() => {
+                let used = available
+                    .iter()
+                    .take_while(|b| b.is_ascii_whitespace())
+                    .count();
+                reader.consume(used);
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Consumes the remainder of the current line including its newline
+fn skip_line<B: BufRead>(reader: &mut B) -> io::Result<()> {
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Ok(());
+        }
+        match memchr(b'\n', available) {
+            Some(idx) => {
+                reader.consume(idx + 1);
+                return Ok(());
+            }
+            None => {
+                let used = available.len();
+                reader.consume(used);
+            }
+        }
+    }
+}
+
+/// Returns the size of the content before the first record of `data`.
+///
+/// See [`skip_preamble`] for what is considered part of the preamble.
+pub(crate) fn preamble_len(data: &[u8]) -> usize {
+    let mut rest = data;
+    skip_preamble(&mut rest).expect("reading from a slice cannot fail");
+    data.len() - rest.len()
+}
+
+/// Returns the end of the line starting at `start`
+/// excluding its newline terminator.
+pub(crate) fn line_end(data: &[u8], start: usize) -> usize {
+    match memchr(b'\n', &data[start..]) {
+        Some(idx) => start + idx,
+        None => data.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_fasta, check_fastq, line_end, locate_lines, preamble_len, read_lines, skip_preamble,
+        strip_carriage_returns,
+    };
+    use crate::Strictness;
+    use std::io::BufReader;
+
+    #[test]
+    fn read_buffered_lines() {
+        let mut reader: &[u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\n";
+        let mut bytes = Vec::new();
+        let mut lines = [0; 4];
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 4);
+        assert_eq!(lines, [7, 5, 2, 5]);
+        assert_eq!(bytes, b"@seq.0\nACGT\n+\n1234\n");
+        assert_eq!(reader, b"@seq.1\n");
+    }
+
+    #[test]
+    fn read_lines_across_buffers() {
+        let data: &[u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nAC\n+\n12\n";
+        let mut reader = BufReader::with_capacity(3, data);
+        let mut bytes = Vec::new();
+        let mut lines = [0; 4];
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 4);
+        assert_eq!(lines, [7, 5, 2, 5]);
+        bytes.clear();
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 4);
+        assert_eq!(lines, [7, 3, 2, 3]);
+        assert_eq!(bytes, b"@seq.1\nAC\n+\n12\n");
+    }
+
+    #[test]
+    fn read_truncated_lines() {
+        let mut reader: &[u8] = b">seq.0\n";
+        let mut bytes = Vec::new();
+        let mut lines = [0; 2];
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 1);
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_unterminated_line() {
+        let mut reader: &[u8] = b">seq.0\nACGT";
+        let mut bytes = Vec::new();
+        let mut lines = [0; 2];
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 2);
+        assert_eq!(lines, [7, 5]);
+        assert_eq!(bytes, b">seq.0\nACGT\n");
+    }
+
+    #[test]
+    fn read_crlf_lines() {
+        let data: &[u8] = b"@seq.0\r\nACGT\r\n+\r\n1234\r\n";
+        let mut reader = BufReader::with_capacity(3, data);
+        let mut bytes = Vec::new();
+        let mut lines = [0; 4];
+        assert_eq!(read_lines(&mut reader, &mut bytes, &mut lines).unwrap(), 4);
+        assert_eq!(lines, [7, 5, 2, 5]);
+        assert_eq!(bytes, b"@seq.0\nACGT\n+\n1234\n");
+    }
+
+    #[test]
+    fn locate_borrowed_lines() {
+        let data = b"@seq.0\r\nACGT\n+\n1234\n@seq.1\n";
+        let mut lines = [0..0, 0..0, 0..0, 0..0];
+        assert_eq!(locate_lines(data, &mut lines), (4, 20));
+        assert_eq!(lines, [0..6, 8..12, 13..14, 15..19]);
+
+        let mut lines = [0..0, 0..0];
+        assert_eq!(locate_lines(b">seq.0\nACGT", &mut lines), (2, 11));
+        assert_eq!(lines, [0..6, 7..11]);
+        assert_eq!(locate_lines(b">seq.0\n", &mut lines), (1, 7));
+        assert_eq!(locate_lines(b"", &mut lines), (0, 0));
+    }
+
+    #[test]
+    fn check_record_lines() {
+        assert!(check_fasta(&[b">a", b"AC"], || false).unwrap());
+        assert!(!check_fasta(&[b">a"], || false).unwrap());
+        assert!(!check_fasta(&[b"", b"\r"], || true).unwrap());
+        assert!(check_fasta(&[b"", b""], || false).is_err());
+
+        let truncated: &[&[u8]] = &[b"@a", b"AC"];
+        assert!(!check_fastq(truncated, Strictness::Lenient, 1, || true).unwrap());
+        let why = check_fastq(truncated, Strictness::Standard, 1, || true)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            why,
+            "Record 1: truncated record (expected 4 lines but found 2)"
+        );
+        let fastq: &[&[u8]] = &[b"@a", b"AC", b"+", b"I"];
+        assert!(check_fastq(fastq, Strictness::Lenient, 1, || true).unwrap());
+        assert!(check_fastq(fastq, Strictness::Standard, 1, || true).is_err());
+    }
+
+    #[test]
+    fn strip_only_line_endings() {
+        let mut bytes = b"xx>a\rb\r\nAC\r\n".to_vec();
+        let mut lines = [6, 4];
+        strip_carriage_returns(&mut bytes, 2, &mut lines);
+        assert_eq!(bytes, b"xx>a\rb\nAC\n");
+        assert_eq!(lines, [5, 3]);
+    }
+
+    #[test]
+    fn skip_bom_blank_and_comments() {
+        let data = b"\xEF\xBB\xBF\n# comment\r\n\n; another\n  \n>seq.0\nACGT\n";
+        assert_eq!(preamble_len(data), 29);
+        assert_eq!(&data[29..], b">seq.0\nACGT\n");
+    }
+
+    #[test]
+    fn skip_long_comment_across_buffers() {
+        let data: &[u8] = b"# a long comment line\n\n@seq.0\n";
+        let mut reader = BufReader::with_capacity(4, data);
+        skip_preamble(&mut reader).unwrap();
+        let mut rest = Vec::new();
+        std::io::Read::read_to_end(&mut reader, &mut rest).unwrap();
+        assert_eq!(rest, b"@seq.0\n");
+    }
+
+    #[test]
+    fn no_preamble() {
+        assert_eq!(preamble_len(b">seq.0\n#ACGT\n"), 0);
+        assert_eq!(preamble_len(b""), 0);
+        assert_eq!(preamble_len(b"\n# only a comment"), 17);
+    }
+
+    #[test]
+    fn find_line_end() {
+        let data = b"seq.0\nACGT";
+        assert_eq!(line_end(data, 0), 5);
+        assert_eq!(line_end(data, 6), 10);
+    }
+}
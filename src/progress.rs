@@ -0,0 +1,300 @@
+use super::fastx::{FastxRead, Format};
+use super::{ReaderBuilder, Record};
+use anyhow::Result;
+use std::{
+    fs::File,
+    io::Read,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// A [`Read`] wrapper which counts the bytes read through it.
+struct CountingReader<R: Read> {
+    inner: R,
+    count: Arc<AtomicU64>,
+}
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// A snapshot of the progress of a [`ProgressReader`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    records: u64,
+    compressed_bytes: u64,
+    uncompressed_bytes: u64,
+    total_bytes: Option<u64>,
+}
+impl Progress {
+    /// Returns the number of records read
+    #[must_use]
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the number of bytes read from the (possibly compressed) input
+    #[must_use]
+    pub fn compressed_bytes(&self) -> u64 {
+        self.compressed_bytes
+    }
+
+    /// Returns the number of bytes read after decompression
+    #[must_use]
+    pub fn uncompressed_bytes(&self) -> u64 {
+        self.uncompressed_bytes
+    }
+
+    /// Returns the total size of the (possibly compressed) input if known
+    #[must_use]
+    pub fn total_bytes(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    /// Returns the fraction of the input which has been read if its
+    /// total size is known
+    #[must_use]
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total_bytes {
+            Some(0) => Some(1.0),
+            Some(total) => Some((self.compressed_bytes as f64 / total as f64).min(1.0)),
+            None => None,
+        }
+    }
+}
+
+/// A callback invoked with the current [`Progress`]
+type Callback = Box<dyn FnMut(&Progress) + Send>;
+
+/// A reader which tracks how much of its input has been read.
+///
+/// Byte counts include data which has been read into the internal
+/// buffers but not yet parsed, so they can run slightly ahead of the
+/// records returned. Inputs are always read through a buffer (i.e.
+/// uncompressed files are not memory mapped).
+///
+/// ```
+/// use fxread::ProgressReader;
+///
+/// let mut reader = ProgressReader::open("example/sequences.fq.gz").unwrap();
+/// assert_eq!(reader.by_ref().count(), 10);
+///
+/// let progress = reader.progress();
+/// assert_eq!(progress.records(), 10);
+/// assert_eq!(progress.compressed_bytes(), progress.total_bytes().unwrap());
+/// assert!(progress.uncompressed_bytes() > progress.compressed_bytes());
+/// ```
+pub struct ProgressReader {
+    reader: Box<dyn FastxRead<Item = Record> + Send>,
+    compressed: Arc<AtomicU64>,
+    uncompressed: Arc<AtomicU64>,
+    total: Option<u64>,
+    records: u64,
+    callback: Option<(u64, Callback)>,
+    /// The number of records at the most recent callback
+    reported: Option<u64>,
+}
+impl ProgressReader {
+    /// Opens a file path with the default [`ReaderBuilder`] settings
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_with(path, ReaderBuilder::new())
+    }
+
+    /// Opens a file path with the provided [`ReaderBuilder`].
+    ///
+    /// The total size of the input is known for regular files.
+    pub fn open_with<P: AsRef<Path>>(path: P, builder: ReaderBuilder) -> Result<Self> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let total = if metadata.is_file() {
+            Some(metadata.len())
+        } else {
+            None
+        };
+        Self::new(file, total, builder)
+    }
+
+    /// # Usage
+    /// Builds a reader over any object which implements [`Read`] with
+    /// its total size if known.
+    /// ```
+    /// use fxread::{ProgressReader, ReaderBuilder};
+    ///
+    /// let input: &'static [u8] = b">seq.0\nACGT\n";
+    /// let mut reader = ProgressReader::new(input, None, ReaderBuilder::new()).unwrap();
+    /// reader.next().unwrap();
+    /// assert_eq!(reader.progress().uncompressed_bytes(), 12);
+    /// assert!(reader.progress().fraction().is_none());
+    /// ```
+    pub fn new<R>(reader: R, total: Option<u64>, builder: ReaderBuilder) -> Result<Self>
+    where
+        R: Read + Send + 'static,
+    {
+        let compressed = Arc::new(AtomicU64::new(0));
+        let uncompressed = Arc::new(AtomicU64::new(0));
+        let reader = builder.decompress(CountingReader {
+            inner: reader,
+            count: compressed.clone(),
+        })?;
+        let reader = builder.buffered(CountingReader {
+            inner: reader,
+            count: uncompressed.clone(),
+        })?;
+        Ok(Self {
+            reader,
+            compressed,
+            uncompressed,
+            total,
+            records: 0,
+            callback: None,
+            reported: None,
+        })
+    }
+
+    /// # Usage
+    /// Registers a callback which is invoked after every `interval`
+    /// records and once more when the input is exhausted.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero
+    /// ```
+    /// use fxread::ProgressReader;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let inner = seen.clone();
+    /// let reader = ProgressReader::open("example/sequences.fa")
+    ///     .unwrap()
+    ///     .on_progress(4, move |p| inner.lock().unwrap().push(p.records()));
+    /// assert_eq!(reader.count(), 10);
+    /// assert_eq!(*seen.lock().unwrap(), [4, 8, 10]);
+    /// ```
+    #[must_use]
+    pub fn on_progress<F>(mut self, interval: u64, callback: F) -> Self
+    where
+        F: FnMut(&Progress) + Send + 'static,
+    {
+        assert!(interval > 0, "progress interval must be greater than zero");
+        self.callback = Some((interval, Box::new(callback)));
+        self
+    }
+
+    /// Returns a snapshot of the current [`Progress`]
+    #[must_use]
+    pub fn progress(&self) -> Progress {
+        Progress {
+            records: self.records,
+            compressed_bytes: self.compressed.load(Ordering::Relaxed),
+            uncompressed_bytes: self.uncompressed.load(Ordering::Relaxed),
+            total_bytes: self.total,
+        }
+    }
+
+    /// Invokes the callback with the current progress
+    fn report(&mut self) {
+        self.reported = Some(self.records);
+        let progress = self.progress();
+        if let Some((_, callback)) = self.callback.as_mut() {
+            callback(&progress);
+        }
+    }
+}
+
+impl FastxRead for ProgressReader {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        match self.reader.next_record()? {
+            Some(record) => {
+                self.records += 1;
+                let interval = self.callback.as_ref().map(|(interval, _)| *interval);
+                if interval.is_some_and(|interval| self.records % interval == 0) {
+                    self.report();
+                }
+                Ok(Some(record))
+            }
+            None => {
+                if self.reported != Some(self.records) {
+                    self.report();
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl Iterator for ProgressReader {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressReader;
+    use crate::{FastxRead, Format, ReaderBuilder};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn plaintext_progress() {
+        let mut reader = ProgressReader::open("example/sequences.fq").unwrap();
+        assert_eq!(reader.format(), Some(Format::Fastq));
+        let total = reader.progress().total_bytes().unwrap();
+        assert_eq!(reader.by_ref().count(), 10);
+        let progress = reader.progress();
+        assert_eq!(progress.compressed_bytes(), total);
+        assert_eq!(progress.uncompressed_bytes(), total);
+        assert_eq!(progress.fraction(), Some(1.0));
+    }
+
+    #[test]
+    fn bytes_increase() {
+        let builder = ReaderBuilder::new().buffer_size(64);
+        let mut reader = ProgressReader::open_with("example/sequences.fa", builder).unwrap();
+        let mut last = 0;
+        while reader.next_record().unwrap().is_some() {
+            let progress = reader.progress();
+            assert!(progress.uncompressed_bytes() >= last);
+            last = progress.uncompressed_bytes();
+        }
+        assert!(reader.progress().fraction().unwrap() == 1.0);
+    }
+
+    #[test]
+    fn callback_reports_final() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let inner = seen.clone();
+        let mut reader = ProgressReader::open("example/sequences.fq.zst")
+            .unwrap()
+            .on_progress(5, move |p| inner.lock().unwrap().push(p.records()));
+        assert_eq!(reader.by_ref().count(), 10);
+        assert!(reader.next_record().unwrap().is_none());
+        assert_eq!(*seen.lock().unwrap(), [5, 10]);
+    }
+
+    #[test]
+    fn empty_input() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let inner = seen.clone();
+        let builder = ReaderBuilder::new().allow_empty(true);
+        let input: &'static [u8] = b"";
+        let reader = ProgressReader::new(input, Some(0), builder)
+            .unwrap()
+            .on_progress(1, move |p| inner.lock().unwrap().push(p.records()));
+        assert_eq!(reader.count(), 0);
+        assert_eq!(*seen.lock().unwrap(), [0]);
+    }
+}
@@ -0,0 +1,589 @@
+use super::fastx::FastxRead;
+use super::record::Record;
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+/// The offset of Phred+33 encoded quality scores
+const PHRED_OFFSET: u8 = 33;
+
+/// The number of distinct Phred scores tracked (0 to 93)
+const QUALITIES: usize = 94;
+
+/// Sequences longer than this are truncated before duplication tracking
+const TRACKED_LENGTH: usize = 75;
+
+/// The prefix length kept of sequences longer than [`TRACKED_LENGTH`]
+const TRUNCATED_LENGTH: usize = 50;
+
+/// Returns the Phred score of a quality character clamped to 0..=93
+fn phred(qual: u8) -> usize {
+    usize::from(qual.saturating_sub(PHRED_OFFSET)).min(QUALITIES - 1)
+}
+
+/// Returns the index of a base in the A, C, G, T, N order
+fn base_index(base: u8) -> usize {
+    match base {
+        b'A' | b'a' => 0,
+        b'C' | b'c' => 1,
+        b'G' | b'g' => 2,
+        b'T' | b't' | b'U' | b'u' => 3,
+        _ => 4,
+    }
+}
+
+/// Returns the smallest score whose cumulative count reaches `fraction`
+/// of the histogram
+fn quantile(histogram: &[u64], total: u64, fraction: f64) -> u8 {
+    let target = (total as f64 * fraction).ceil().max(1.0) as u64;
+    let mut cumulative = 0;
+    for (score, count) in histogram.iter().enumerate() {
+        cumulative += count;
+        if cumulative >= target {
+            return score as u8;
+        }
+    }
+    (histogram.len() - 1) as u8
+}
+
+/// Accumulates FastQC-style quality control profiles of records.
+///
+/// Qualities are assumed to be Phred+33 encoded and fasta records only
+/// contribute to the composition, length and duplication profiles.
+/// Duplication is measured over the first `max_tracked` distinct
+/// sequences (truncated to their first 50 bases when longer than 75),
+/// after which only repeats of tracked sequences are counted.
+///
+/// ```
+/// use fxread::{initialize_reader, Qc};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let report = Qc::from_reader(reader).unwrap().report();
+/// assert_eq!(report.records(), 10);
+/// for cycle in report.per_cycle_quality() {
+///     assert!(cycle.lower_quartile() <= cycle.median());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Qc {
+    records: u64,
+    /// The histogram of Phred scores at each position
+    cycle_qualities: Vec<[u64; QUALITIES]>,
+    /// The A, C, G, T and N counts at each position
+    cycle_bases: Vec<[u64; 5]>,
+    lengths: BTreeMap<usize, u64>,
+    /// The histogram of the (rounded down) mean quality of each read
+    mean_qualities: [u64; QUALITIES],
+    sequences: HashMap<Vec<u8>, u64>,
+    /// The number of records counted in the duplication profile
+    tracked_records: u64,
+    max_tracked: usize,
+    overrepresented_fraction: f64,
+}
+impl Default for Qc {
+    fn default() -> Self {
+        Self {
+            records: 0,
+            cycle_qualities: Vec::new(),
+            cycle_bases: Vec::new(),
+            lengths: BTreeMap::new(),
+            mean_qualities: [0; QUALITIES],
+            sequences: HashMap::new(),
+            tracked_records: 0,
+            max_tracked: 100_000,
+            overrepresented_fraction: 0.001,
+        }
+    }
+}
+impl Qc {
+    /// Creates an empty [`Qc`] tracking up to 100,000 distinct sequences
+    /// and reporting sequences above 0.1% of reads as overrepresented
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of distinct sequences tracked for duplication
+    #[must_use]
+    pub fn max_tracked(mut self, max_tracked: usize) -> Self {
+        self.max_tracked = max_tracked;
+        self
+    }
+
+    /// Sets the fraction of reads above which a sequence is reported as
+    /// overrepresented
+    #[must_use]
+    pub fn overrepresented_fraction(mut self, fraction: f64) -> Self {
+        self.overrepresented_fraction = fraction;
+        self
+    }
+
+    /// Accumulates the profiles of every record of a reader
+    pub fn from_reader<R: FastxRead>(mut reader: R) -> Result<Self> {
+        let mut qc = Self::new();
+        while let Some(record) = reader.next_record()? {
+            qc.add(&record);
+        }
+        Ok(qc)
+    }
+
+    /// Adds a record to the profiles
+    pub fn add(&mut self, record: &Record) {
+        let seq = record.seq();
+        self.records += 1;
+        *self.lengths.entry(seq.len()).or_default() += 1;
+
+        if self.cycle_bases.len() < seq.len() {
+            self.cycle_bases.resize(seq.len(), [0; 5]);
+        }
+        for (counts, base) in self.cycle_bases.iter_mut().zip(seq) {
+            counts[base_index(*base)] += 1;
+        }
+
+        if let Some(qual) = record.qual().filter(|q| !q.is_empty()) {
+            if self.cycle_qualities.len() < qual.len() {
+                self.cycle_qualities.resize(qual.len(), [0; QUALITIES]);
+            }
+            let mut sum = 0;
+            for (histogram, q) in self.cycle_qualities.iter_mut().zip(qual) {
+                histogram[phred(*q)] += 1;
+                sum += phred(*q);
+            }
+            self.mean_qualities[sum / qual.len()] += 1;
+        }
+
+        let key = if seq.len() > TRACKED_LENGTH {
+            &seq[..TRUNCATED_LENGTH]
+        } else {
+            seq
+        };
+        self.track(key, 1);
+    }
+
+    /// Counts occurrences of a sequence if it is (or can still be) tracked
+    fn track(&mut self, key: &[u8], count: u64) {
+        if let Some(existing) = self.sequences.get_mut(key) {
+            *existing += count;
+        } else if self.sequences.len() < self.max_tracked {
+            self.sequences.insert(key.to_vec(), count);
+        } else {
+            return;
+        }
+        self.tracked_records += count;
+    }
+
+    /// # Usage
+    /// Combines the profiles of another [`Qc`] (e.g. of another thread)
+    /// into these
+    /// ```
+    /// use fxread::{initialize_reader, Qc};
+    ///
+    /// let mut qc = Qc::from_reader(initialize_reader("example/sequences.fq").unwrap()).unwrap();
+    /// let other = Qc::from_reader(initialize_reader("example/sequences.fq.gz").unwrap()).unwrap();
+    /// qc.merge(&other);
+    /// let report = qc.report();
+    /// assert_eq!(report.records(), 20);
+    /// assert!(report.duplication_levels().iter().all(|(copies, _)| *copies == 2));
+    /// ```
+    pub fn merge(&mut self, other: &Qc) {
+        self.records += other.records;
+        if self.cycle_qualities.len() < other.cycle_qualities.len() {
+            self.cycle_qualities
+                .resize(other.cycle_qualities.len(), [0; QUALITIES]);
+        }
+        for (a, b) in self.cycle_qualities.iter_mut().zip(&other.cycle_qualities) {
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+        }
+        if self.cycle_bases.len() < other.cycle_bases.len() {
+            self.cycle_bases.resize(other.cycle_bases.len(), [0; 5]);
+        }
+        for (a, b) in self.cycle_bases.iter_mut().zip(&other.cycle_bases) {
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+        }
+        for (length, count) in &other.lengths {
+            *self.lengths.entry(*length).or_default() += count;
+        }
+        self.mean_qualities
+            .iter_mut()
+            .zip(&other.mean_qualities)
+            .for_each(|(a, b)| *a += b);
+        for (seq, count) in &other.sequences {
+            self.track(seq, *count);
+        }
+    }
+
+    /// Summarizes the accumulated profiles into a [`QcReport`]
+    #[must_use]
+    pub fn report(&self) -> QcReport {
+        let per_cycle_quality = self
+            .cycle_qualities
+            .iter()
+            .enumerate()
+            .map(|(idx, histogram)| {
+                let total: u64 = histogram.iter().sum();
+                let sum: u64 = histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(q, count)| q as u64 * count)
+                    .sum();
+                CycleQuality {
+                    cycle: idx + 1,
+                    mean: sum as f64 / total as f64,
+                    lower_quartile: quantile(histogram, total, 0.25),
+                    median: quantile(histogram, total, 0.5),
+                    upper_quartile: quantile(histogram, total, 0.75),
+                }
+            })
+            .collect();
+        let per_cycle_composition = self
+            .cycle_bases
+            .iter()
+            .enumerate()
+            .map(|(idx, counts)| {
+                let total: u64 = counts.iter().sum();
+                let fraction = |i: usize| counts[i] as f64 / total as f64;
+                CycleComposition {
+                    cycle: idx + 1,
+                    a: fraction(0),
+                    c: fraction(1),
+                    g: fraction(2),
+                    t: fraction(3),
+                    n: fraction(4),
+                }
+            })
+            .collect();
+        let mean_quality_distribution = self
+            .mean_qualities
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(q, count)| (q as u8, *count))
+            .collect();
+
+        let mut levels = BTreeMap::new();
+        for count in self.sequences.values() {
+            *levels.entry(*count).or_default() += 1;
+        }
+        let mut overrepresented: Vec<_> = self
+            .sequences
+            .iter()
+            .filter(|(_, count)| {
+                **count as f64 > self.overrepresented_fraction * self.records as f64
+            })
+            .map(|(seq, count)| Overrepresented {
+                sequence: String::from_utf8_lossy(seq).into_owned(),
+                count: *count,
+                fraction: *count as f64 / self.records as f64,
+            })
+            .collect();
+        overrepresented.sort_by(|a, b| b.count.cmp(&a.count).then(a.sequence.cmp(&b.sequence)));
+
+        QcReport {
+            records: self.records,
+            per_cycle_quality,
+            per_cycle_composition,
+            length_distribution: self.lengths.iter().map(|(l, c)| (*l, *c)).collect(),
+            mean_quality_distribution,
+            tracked_records: self.tracked_records,
+            duplication_levels: levels.into_iter().collect(),
+            overrepresented,
+        }
+    }
+}
+
+/// The quality distribution at one position of the reads
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CycleQuality {
+    cycle: usize,
+    mean: f64,
+    lower_quartile: u8,
+    median: u8,
+    upper_quartile: u8,
+}
+impl CycleQuality {
+    /// Returns the 1-based position in the reads
+    #[must_use]
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Returns the mean Phred score
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Returns the first quartile of the Phred scores
+    #[must_use]
+    pub fn lower_quartile(&self) -> u8 {
+        self.lower_quartile
+    }
+
+    /// Returns the median Phred score
+    #[must_use]
+    pub fn median(&self) -> u8 {
+        self.median
+    }
+
+    /// Returns the third quartile of the Phred scores
+    #[must_use]
+    pub fn upper_quartile(&self) -> u8 {
+        self.upper_quartile
+    }
+}
+
+/// The base composition at one position of the reads
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CycleComposition {
+    cycle: usize,
+    a: f64,
+    c: f64,
+    g: f64,
+    t: f64,
+    n: f64,
+}
+impl CycleComposition {
+    /// Returns the 1-based position in the reads
+    #[must_use]
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    /// Returns the fraction of A bases
+    #[must_use]
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+
+    /// Returns the fraction of C bases
+    #[must_use]
+    pub fn c(&self) -> f64 {
+        self.c
+    }
+
+    /// Returns the fraction of G bases
+    #[must_use]
+    pub fn g(&self) -> f64 {
+        self.g
+    }
+
+    /// Returns the fraction of T (or U) bases
+    #[must_use]
+    pub fn t(&self) -> f64 {
+        self.t
+    }
+
+    /// Returns the fraction of N (or any other) bases
+    #[must_use]
+    pub fn n(&self) -> f64 {
+        self.n
+    }
+}
+
+/// A sequence making up an unexpectedly large fraction of the reads
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Overrepresented {
+    sequence: String,
+    count: u64,
+    fraction: f64,
+}
+impl Overrepresented {
+    /// Returns the (possibly truncated) sequence
+    #[must_use]
+    pub fn sequence(&self) -> &str {
+        &self.sequence
+    }
+
+    /// Returns the number of reads with the sequence
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the fraction of all reads with the sequence
+    #[must_use]
+    pub fn fraction(&self) -> f64 {
+        self.fraction
+    }
+}
+
+/// The results of a [`Qc`].
+///
+/// With the `serde` feature enabled the report implements
+/// `serde::Serialize` for export to e.g. JSON.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct QcReport {
+    records: u64,
+    per_cycle_quality: Vec<CycleQuality>,
+    per_cycle_composition: Vec<CycleComposition>,
+    length_distribution: Vec<(usize, u64)>,
+    mean_quality_distribution: Vec<(u8, u64)>,
+    tracked_records: u64,
+    duplication_levels: Vec<(u64, u64)>,
+    overrepresented: Vec<Overrepresented>,
+}
+impl QcReport {
+    /// Returns the number of records
+    #[must_use]
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the quality distribution at each position
+    #[must_use]
+    pub fn per_cycle_quality(&self) -> &[CycleQuality] {
+        &self.per_cycle_quality
+    }
+
+    /// Returns the base composition at each position
+    #[must_use]
+    pub fn per_cycle_composition(&self) -> &[CycleComposition] {
+        &self.per_cycle_composition
+    }
+
+    /// Returns the number of records of each length
+    #[must_use]
+    pub fn length_distribution(&self) -> &[(usize, u64)] {
+        &self.length_distribution
+    }
+
+    /// Returns the number of reads with each (rounded down) mean
+    /// Phred score
+    #[must_use]
+    pub fn mean_quality_distribution(&self) -> &[(u8, u64)] {
+        &self.mean_quality_distribution
+    }
+
+    /// Returns the number of records included in the duplication levels
+    #[must_use]
+    pub fn tracked_records(&self) -> u64 {
+        self.tracked_records
+    }
+
+    /// Returns the number of distinct sequences seen each number of times
+    #[must_use]
+    pub fn duplication_levels(&self) -> &[(u64, u64)] {
+        &self.duplication_levels
+    }
+
+    /// Returns the fraction of tracked records which would remain after
+    /// removing duplicates
+    #[must_use]
+    pub fn deduplicated_fraction(&self) -> Option<f64> {
+        let distinct: u64 = self.duplication_levels.iter().map(|(_, n)| n).sum();
+        (self.tracked_records > 0).then(|| distinct as f64 / self.tracked_records as f64)
+    }
+
+    /// Returns the overrepresented sequences from most to least common
+    #[must_use]
+    pub fn overrepresented(&self) -> &[Overrepresented] {
+        &self.overrepresented
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quantile, Qc};
+    use crate::{FastaReader, FastqReader, Record};
+
+    #[test]
+    fn quantiles() {
+        let histogram = [1, 1, 1, 1];
+        assert_eq!(quantile(&histogram, 4, 0.25), 0);
+        assert_eq!(quantile(&histogram, 4, 0.5), 1);
+        assert_eq!(quantile(&histogram, 4, 0.75), 2);
+        assert_eq!(quantile(&[0, 0, 3], 3, 0.5), 2);
+    }
+
+    #[test]
+    fn cycle_profiles() {
+        // '5' = 20, '?' = 30, '+' = 10, 'I' = 40
+        let input: &'static [u8] = b"@a\nACG\n+\n5?I\n@b\nANG\n+\n+?I\n@c\nA\n+\n5\n";
+        let report = Qc::from_reader(FastqReader::new(input)).unwrap().report();
+        assert_eq!(report.records(), 3);
+
+        let quality = report.per_cycle_quality();
+        assert_eq!(quality.len(), 3);
+        assert!((quality[0].mean() - 50.0 / 3.0).abs() < 1e-9);
+        assert_eq!(quality[0].median(), 20);
+        assert_eq!(quality[0].lower_quartile(), 10);
+        assert_eq!(quality[2].cycle(), 3);
+        assert_eq!(quality[2].upper_quartile(), 40);
+
+        let composition = report.per_cycle_composition();
+        assert_eq!(composition[0].a(), 1.0);
+        assert_eq!(composition[1].c(), 0.5);
+        assert_eq!(composition[1].n(), 0.5);
+        assert_eq!(report.length_distribution(), [(1, 1), (3, 2)]);
+        // means of 30, 26 and 20
+        assert_eq!(
+            report.mean_quality_distribution(),
+            [(20, 1), (26, 1), (30, 1)]
+        );
+    }
+
+    #[test]
+    fn duplication() {
+        let input: &'static [u8] = b">a\nACGT\n>b\nACGT\n>c\nACGT\n>d\nTTTT\n>e\nGGGG\n>f\nGGGG\n";
+        let mut qc = Qc::new().max_tracked(2).overrepresented_fraction(0.3);
+        FastaReader::new(input).for_each(|r| qc.add(&r));
+        let report = qc.report();
+        // GGGG is never tracked
+        assert_eq!(report.tracked_records(), 4);
+        assert_eq!(report.duplication_levels(), [(1, 1), (3, 1)]);
+        assert_eq!(report.deduplicated_fraction(), Some(0.5));
+        assert_eq!(report.overrepresented().len(), 1);
+        assert_eq!(report.overrepresented()[0].sequence(), "ACGT");
+        assert_eq!(report.overrepresented()[0].fraction(), 0.5);
+        assert!(report.per_cycle_quality().is_empty());
+    }
+
+    #[test]
+    fn long_reads_truncated() {
+        let seq = [b'A'; 100];
+        let mut other = seq;
+        other[80] = b'C';
+        let mut qc = Qc::new();
+        qc.add(&Record::new_fasta_from_parts(b"a", &seq).unwrap());
+        qc.add(&Record::new_fasta_from_parts(b"b", &other).unwrap());
+        let report = qc.report();
+        assert_eq!(report.duplication_levels(), [(2, 1)]);
+        assert_eq!(report.overrepresented()[0].sequence().len(), 50);
+    }
+
+    #[test]
+    fn merge_matches_single_pass() {
+        let records = [
+            Record::new_fastq_from_parts(b"a", b"ACGT", b"II5+").unwrap(),
+            Record::new_fastq_from_parts(b"b", b"ACGTAA", b"5555II").unwrap(),
+            Record::new_fasta_from_parts(b"c", b"ACGT").unwrap(),
+        ];
+        let mut whole = Qc::new();
+        records.iter().for_each(|r| whole.add(r));
+
+        let mut first = Qc::new();
+        first.add(&records[0]);
+        let mut second = Qc::new();
+        second.add(&records[1]);
+        second.add(&records[2]);
+        first.merge(&second);
+        assert_eq!(first.report(), whole.report());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_report() {
+        let input: &'static [u8] = b"@a\nAC\n+\nII\n";
+        let report = Qc::from_reader(FastqReader::new(input)).unwrap().report();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["records"], 1);
+        assert_eq!(json["per_cycle_quality"][1]["median"], 40);
+        assert_eq!(json["length_distribution"][0], serde_json::json!([2, 1]));
+    }
+}
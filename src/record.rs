@@ -1,3 +1,4 @@
+use super::alphabet::Alphabet;
 use anyhow::{bail, Result};
 use std::ops::{Range, RangeInclusive};
 
@@ -27,7 +28,7 @@ impl MyRange for RangeInclusive<i32> {
 /// An instance of a Fastx Record.
 /// This is a two attribute object containing the sequence
 /// ID and the Sequence.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Record {
     data: Vec<u8>,
     /// The index of the ID size
@@ -122,7 +123,6 @@ impl Record {
     /// assert_eq!(record.id(), b"seq.0");
     /// assert_eq!(record.seq(), b"ACGT");
     /// ```
-    #[must_use]
     pub fn new_fasta_from_parts(id: &[u8], seq: &[u8]) -> Result<Self> {
         let mut data = Vec::with_capacity(id.len() + seq.len() + 2);
         if id.starts_with(b">") {
@@ -166,7 +166,6 @@ impl Record {
     /// assert_eq!(record.seq(), b"ACGT");
     /// assert_eq!(record.qual().unwrap(), b"1234");
     /// ```
-    #[must_use]
     pub fn new_fastq_from_parts(id: &[u8], seq: &[u8], qual: &[u8]) -> Result<Self> {
         let mut data = Vec::with_capacity(id.len() + seq.len() + qual.len() + 4);
         if id.starts_with(b"@") {
@@ -247,23 +246,16 @@ impl Record {
     /// Returns the Range of the '+' region of a fastq
     #[must_use]
     pub fn plus_range(&self) -> Option<Range<usize>> {
-        match self.plus {
-            Some(plus) => Some(1 + self.id + self.seq..self.id + self.seq + plus),
-            None => None,
-        }
+        self.plus
+            .map(|plus| 1 + self.id + self.seq..self.id + self.seq + plus)
     }
 
     /// Returns the Range of the quality score if it exists
     #[must_use]
     pub fn qual_range(&self) -> Option<Range<usize>> {
-        let plus = match self.plus {
-            Some(plus) => plus,
-            None => return None,
-        };
-        match self.qual {
-            Some(qual) => Some(1 + self.id + self.seq + plus..self.id + self.seq + plus + qual),
-            None => None,
-        }
+        let plus = self.plus?;
+        self.qual
+            .map(|qual| 1 + self.id + self.seq + plus..self.id + self.seq + plus + qual)
     }
 
     /// Returns a reference of the sequence ID
@@ -316,6 +308,9 @@ impl Record {
     }
 
     /// Returns a reference to the raw data underlying the record
+    ///
+    /// Readers normalize Windows line endings, so the data of a record
+    /// read from a "\r\n" terminated file only contains '\n' terminators.
     #[must_use]
     pub fn data(&self) -> &[u8] {
         &self.data
@@ -325,10 +320,25 @@ impl Record {
     /// or composed of unexpected nucleotides
     #[must_use]
     pub fn valid(&self) -> bool {
+        self.valid_with(&Alphabet::nucleotide())
+    }
+
+    /// # Usage
+    /// Validates that the record is not partially constructed
+    /// and its sequence is composed of characters in the [`Alphabet`]
+    /// ```
+    /// use fxread::{Alphabet, Record};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"ACGTRY").unwrap();
+    /// assert!(!record.valid_with(&Alphabet::dna()));
+    /// assert!(record.valid_with(&Alphabet::dna_iupac()));
+    /// ```
+    #[must_use]
+    pub fn valid_with(&self, alphabet: &Alphabet) -> bool {
         if self.empty() {
             false
         } else {
-            self.valid_sequence()
+            alphabet.validate(self.seq())
         }
     }
 
@@ -342,23 +352,41 @@ impl Record {
     }
 
     /// Reverse Complements the sequence
+    ///
+    /// `N` and `n` are their own complement and are kept unchanged.
     #[must_use]
     pub fn seq_rev_comp(&self) -> Vec<u8> {
         self.seq()
             .iter()
             .rev()
-            .map(|c| if c & 2 == 0 { c ^ 21 } else { c ^ 4 })
+            .map(|c| match c {
+                b'N' | b'n' => *c,
+                c if c & 2 == 0 => c ^ 21,
+                c => c ^ 4,
+            })
             .collect()
     }
 
     /// Converts all non-ACGTN nucleotides to N
     pub fn fix(&mut self) {
+        self.fix_with(&Alphabet::dna());
+    }
+
+    /// # Usage
+    /// Converts all characters outside of the [`Alphabet`] to
+    /// its unknown character (e.g. 'X' for proteins)
+    /// ```
+    /// use fxread::{Alphabet, Record};
+    ///
+    /// let mut record = Record::new_fasta_from_parts(b"seq.0", b"MK1W").unwrap();
+    /// record.fix_with(&Alphabet::protein());
+    /// assert_eq!(record.seq(), b"MKXW");
+    /// ```
+    pub fn fix_with(&mut self, alphabet: &Alphabet) {
+        let unknown = alphabet.unknown();
         self.seq_mut().iter_mut().for_each(|c| {
-            if !matches!(
-                c,
-                b'A' | b'a' | b'C' | b'c' | b'G' | b'g' | b'T' | b't' | b'N' | b'n'
-            ) {
-                *c = b'N';
+            if !alphabet.contains(*c) {
+                *c = unknown;
             }
         });
     }
@@ -393,6 +421,32 @@ impl Record {
         }
     }
 
+    /// # Usage
+    /// Replaces the ID (the header without its marker)
+    ///
+    /// The '+' line of a fastq is reset to a bare '+' so it cannot
+    /// repeat the old ID.
+    /// Returns an error if the ID contains a line break
+    /// ```
+    /// let mut record = fxread::Record::new_fastq_from_parts(b"seq.0", b"ACGT", b"1234").unwrap();
+    /// record.set_id(b"seq.0_ACGT").unwrap();
+    /// assert_eq!(record.id(), b"seq.0_ACGT");
+    /// assert_eq!(record.plus().unwrap(), b"+");
+    /// assert_eq!(record.qual().unwrap(), b"1234");
+    /// ```
+    pub fn set_id(&mut self, id: &[u8]) -> Result<()> {
+        if id.iter().any(|c| matches!(c, b'\n' | b'\r')) {
+            bail!("ID cannot contain a line break");
+        }
+        if let Some(range) = self.plus_range() {
+            self.data.splice(range, std::iter::once(b'+'));
+            self.plus = Some(2);
+        }
+        self.data.splice(self.id_range(), id.iter().copied());
+        self.id = id.len() + 1;
+        Ok(())
+    }
+
     /// Inserts nucleotides into the sequence at the specified index
     /// and the corresponding quality scores if present
     /// Returns an error if the index is greater than the sequence length
@@ -488,43 +542,24 @@ impl Record {
         Ok(())
     }
 
-    /// Validates whether sequence is composed
-    /// of valid nucleotides
-    fn valid_sequence(&self) -> bool {
-        self.seq().iter().all(|b| {
-            matches!(
-                b,
-                b'A' | b'a' | b'C' | b'c' | b'G' | b'g' | b'T' | b't' | b'N' | b'n' | b'U' | b'u'
-            )
-        })
-    }
-
     /// Underlying record as str
-    #[must_use]
     pub fn as_str_checked(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.data())
     }
 
     /// ID as str
-    #[must_use]
     pub fn id_str_checked(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.id())
     }
 
     /// Sequence as str
-    #[must_use]
     pub fn seq_str_checked(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.seq())
     }
 
     /// Quality as str
-    #[must_use]
     pub fn qual_str_checked(&self) -> Option<Result<&str, std::str::Utf8Error>> {
-        if let Some(qual) = self.qual() {
-            Some(std::str::from_utf8(qual))
-        } else {
-            None
-        }
+        self.qual().map(std::str::from_utf8)
     }
 
     /// Underlying record as str unchecked (may panic if invalid utf8)
@@ -552,21 +587,145 @@ impl Record {
     }
 }
 
+/// A borrowed instance of a Fastx Record.
+///
+/// This references its data directly from an underlying buffer such
+/// as a memory mapped file and can be converted into an owned [`Record`]
+/// with [`RefRecord::to_record`].
+#[derive(Debug, Clone)]
+pub struct RefRecord<'a> {
+    data: &'a [u8],
+    /// The range of the ID within the data
+    id: Range<usize>,
+    /// The range of the sequence within the data
+    seq: Range<usize>,
+    /// The range of the plus line (if fastq)
+    plus: Option<Range<usize>>,
+    /// The range of the quality scores (if fastq)
+    qual: Option<Range<usize>>,
+}
+impl<'a> RefRecord<'a> {
+    /// Creates a new borrowed fasta record from the raw record data
+    /// and the ranges of each line excluding their terminators.
+    pub(crate) fn new_fasta(data: &'a [u8], id: Range<usize>, seq: Range<usize>) -> Self {
+        Self {
+            data,
+            id,
+            seq,
+            plus: None,
+            qual: None,
+        }
+    }
+
+    /// Creates a new borrowed fastq record from the raw record data
+    /// and the ranges of each line excluding their terminators.
+    pub(crate) fn new_fastq(
+        data: &'a [u8],
+        id: Range<usize>,
+        seq: Range<usize>,
+        plus: Range<usize>,
+        qual: Range<usize>,
+    ) -> Self {
+        Self {
+            data,
+            id,
+            seq,
+            plus: Some(plus),
+            qual: Some(qual),
+        }
+    }
+
+    /// Checks if `[RefRecord]` is a fasta
+    #[must_use]
+    pub fn is_fasta(&self) -> bool {
+        self.plus.is_none() & self.qual.is_none()
+    }
+
+    /// Checks if `[RefRecord]` is a fastq
+    #[must_use]
+    pub fn is_fastq(&self) -> bool {
+        self.plus.is_some() & self.qual.is_some()
+    }
+
+    /// Returns a reference of the sequence ID
+    #[must_use]
+    pub fn id(&self) -> &'a [u8] {
+        &self.data[self.id.clone()]
+    }
+
+    /// Returns a reference of the sequence
+    #[must_use]
+    pub fn seq(&self) -> &'a [u8] {
+        &self.data[self.seq.clone()]
+    }
+
+    /// Returns a reference of the '+' region of a fastq
+    #[must_use]
+    pub fn plus(&self) -> Option<&'a [u8]> {
+        self.plus.clone().map(|range| &self.data[range])
+    }
+
+    /// Returns a reference of the quality scores if they exist
+    #[must_use]
+    pub fn qual(&self) -> Option<&'a [u8]> {
+        self.qual.clone().map(|range| &self.data[range])
+    }
+
+    /// Returns a reference to the raw data underlying the record
+    ///
+    /// Unlike [`Record::data`] this is not normalized and may contain
+    /// "\r\n" line endings. The individual fields never include the '\r'.
+    #[must_use]
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// # Usage
+    /// Copies the borrowed record into an owned [`Record`].
+    /// A missing newline on the final line is added.
+    #[must_use]
+    pub fn to_record(&self) -> Record {
+        let mut data = Vec::with_capacity(self.data.len() + 1);
+        data.push(self.data[0]);
+        data.extend_from_slice(self.id());
+        data.push(b'\n');
+        data.extend_from_slice(self.seq());
+        data.push(b'\n');
+        match (self.plus(), self.qual()) {
+            (Some(plus), Some(qual)) => {
+                data.extend_from_slice(plus);
+                data.push(b'\n');
+                data.extend_from_slice(qual);
+                data.push(b'\n');
+                Record::new_fastq(
+                    data,
+                    self.id.len() + 1,
+                    self.seq.len() + 1,
+                    plus.len() + 1,
+                    qual.len() + 1,
+                )
+            }
+            _ => Record::new_fasta(data, self.id.len() + 1, self.seq.len() + 1),
+        }
+    }
+}
+
 impl Default for Record {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Into<String> for Record {
-    fn into(self) -> String {
-        self.as_str().to_string()
+impl From<Record> for String {
+    fn from(record: Record) -> Self {
+        record.as_str().to_string()
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::Record;
+    use crate::Alphabet;
 
     fn gen_valid_fasta() -> (Vec<u8>, usize, usize) {
         (b">seq.0\nACGT\n".to_vec(), 6, 5)
@@ -690,6 +849,12 @@ mod test {
         assert_eq!(record.seq_rev_comp(), b"tagccgat");
     }
 
+    #[test]
+    fn reverse_complement_n() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ANCgn").unwrap();
+        assert_eq!(record.seq_rev_comp(), b"ncGNT");
+    }
+
     #[test]
     fn invalid_fix_fasta() {
         let (fasta, id, seq) = gen_invalid_fasta();
@@ -714,6 +879,35 @@ mod test {
         assert_eq!(record.seq(), b"ANCN");
     }
 
+    #[test]
+    fn valid_rna() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ACGU").unwrap();
+        assert!(record.valid());
+        assert!(record.valid_with(&Alphabet::rna()));
+        assert!(!record.valid_with(&Alphabet::dna()));
+    }
+
+    #[test]
+    fn valid_with_empty() {
+        let record = Record::new();
+        assert!(!record.valid_with(&Alphabet::protein()));
+    }
+
+    #[test]
+    fn fix_with_iupac() {
+        let mut record = Record::new_fastq_from_parts(b"seq.0", b"ARYZ-", b"12345").unwrap();
+        record.fix_with(&Alphabet::dna_iupac());
+        assert_eq!(record.seq(), b"ARYN-");
+        assert_eq!(record.qual().unwrap(), b"12345");
+    }
+
+    #[test]
+    fn fix_with_custom() {
+        let mut record = Record::new_fasta_from_parts(b"seq.0", b"acgt").unwrap();
+        record.fix_with(&Alphabet::custom(b"ACGT", b'-'));
+        assert_eq!(record.seq(), b"----");
+    }
+
     #[test]
     fn upper_inplace() {
         let (fasta, id, seq) = gen_valid_fasta_lower();
@@ -1041,4 +1235,30 @@ mod test {
         let seq = b"ACGT\n";
         assert!(Record::new_fasta_from_parts(id, seq).is_err());
     }
+
+    #[test]
+    fn set_id() {
+        let (fastq, id, seq, plus, qual) = gen_valid_fastq();
+        let mut record = Record::new_fastq(fastq, id, seq, plus, qual);
+        record.set_id(b"a longer id").unwrap();
+        assert_eq!(record.id(), b"a longer id");
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(record.plus().unwrap(), b"+");
+        record.set_id(b"s").unwrap();
+        assert_eq!(record.id(), b"s");
+        assert_eq!(record.seq(), b"ACGT");
+        assert_eq!(record.qual().unwrap().len(), 4);
+        assert!(record.set_id(b"a\nb").is_err());
+    }
+
+    #[test]
+    fn set_id_resets_plus() {
+        let data = b"@seq.0\nACGT\n+seq.0\n1234\n".to_vec();
+        let mut record = Record::new_fastq(data, 6, 5, 7, 5);
+        assert_eq!(record.plus().unwrap(), b"+seq.0");
+        record.set_id(b"seq.0_AC").unwrap();
+        assert_eq!(record.plus().unwrap(), b"+");
+        assert_eq!(record.qual().unwrap(), b"1234");
+        assert_eq!(record.data(), b"@seq.0_AC\nACGT\n+\n1234\n");
+    }
 }
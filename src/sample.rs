@@ -0,0 +1,384 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use anyhow::{bail, Result};
+
+/// A small seeded pseudo random number generator (SplitMix64).
+///
+/// Sampling only needs reproducibility and a uniform distribution,
+/// not cryptographic quality, so no external generator is required.
+#[derive(Debug, Clone)]
+struct SplitMix64(u64);
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniform float in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a uniform integer in `[0, n)`
+    fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+}
+
+/// Validates that a sampling fraction is within `[0, 1]`
+fn check_fraction(fraction: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&fraction) {
+        bail!(
+            "Sampling fraction must be between 0 and 1 but found {}",
+            fraction
+        );
+    }
+    Ok(())
+}
+
+/// Reads the next pair of records and checks both inputs end together
+pub(crate) fn next_pair<R1, R2>(r1: &mut R1, r2: &mut R2) -> Result<Option<(Record, Record)>>
+where
+    R1: FastxRead,
+    R2: FastxRead,
+{
+    match (r1.next_record()?, r2.next_record()?) {
+        (Some(a), Some(b)) => Ok(Some((a, b))),
+        (None, None) => Ok(None),
+        _ => bail!("Paired inputs have different numbers of records"),
+    }
+}
+
+/// A reader adaptor which keeps each record with a fixed probability.
+///
+/// The selection only depends on the seed and the position of each
+/// record, so the same seed always yields the same sample.
+///
+/// ```
+/// use fxread::{initialize_reader, FractionSampler};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let first: Vec<_> = FractionSampler::new(reader, 0.5, 42).unwrap().collect();
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let second: Vec<_> = FractionSampler::new(reader, 0.5, 42).unwrap().collect();
+/// assert_eq!(first.len(), second.len());
+/// ```
+pub struct FractionSampler<R: FastxRead> {
+    reader: R,
+    fraction: f64,
+    rng: SplitMix64,
+}
+impl<R: FastxRead> FractionSampler<R> {
+    /// Creates a new [`FractionSampler`] keeping each record of `reader`
+    /// with probability `fraction`.
+    ///
+    /// Returns an error if the fraction is not within `[0, 1]`.
+    pub fn new(reader: R, fraction: f64, seed: u64) -> Result<Self> {
+        check_fraction(fraction)?;
+        Ok(Self {
+            reader,
+            fraction,
+            rng: SplitMix64(seed),
+        })
+    }
+}
+
+impl<R: FastxRead> FastxRead for FractionSampler<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        while let Some(record) = self.reader.next_record()? {
+            if self.rng.next_f64() < self.fraction {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for FractionSampler<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+/// # Usage
+/// Selects exactly `n` records uniformly at random (or every record if
+/// there are fewer) with reservoir sampling.
+///
+/// The selected records are returned in their input order and the same
+/// seed always yields the same sample.
+/// ```
+/// use fxread::{initialize_reader, reservoir_sample};
+///
+/// let reader = initialize_reader("example/sequences.fa").unwrap();
+/// let sample = reservoir_sample(reader, 3, 7).unwrap();
+/// assert_eq!(sample.len(), 3);
+/// ```
+pub fn reservoir_sample<R: FastxRead>(mut reader: R, n: usize, seed: u64) -> Result<Vec<Record>> {
+    let mut rng = SplitMix64(seed);
+    let mut reservoir = Vec::new();
+    let mut seen = 0;
+    while let Some(record) = reader.next_record()? {
+        reserve(&mut reservoir, &mut rng, n, seen, record);
+        seen += 1;
+    }
+    Ok(ordered(reservoir))
+}
+
+/// A reader adaptor over paired inputs (e.g. R1 and R2) which keeps
+/// each pair of mates together with a fixed probability.
+///
+/// ```
+/// use fxread::{initialize_reader, PairedFractionSampler};
+///
+/// let r1 = initialize_reader("example/sequences.fq").unwrap();
+/// let r2 = initialize_reader("example/sequences.fq.gz").unwrap();
+/// for (a, b) in PairedFractionSampler::new(r1, r2, 0.5, 3).unwrap() {
+///     assert_eq!(a.id(), b.id());
+/// }
+/// ```
+pub struct PairedFractionSampler<R1: FastxRead, R2: FastxRead> {
+    r1: R1,
+    r2: R2,
+    fraction: f64,
+    rng: SplitMix64,
+}
+impl<R1: FastxRead, R2: FastxRead> PairedFractionSampler<R1, R2> {
+    /// Creates a new [`PairedFractionSampler`] keeping each pair of
+    /// records with probability `fraction`.
+    ///
+    /// Returns an error if the fraction is not within `[0, 1]`.
+    pub fn new(r1: R1, r2: R2, fraction: f64, seed: u64) -> Result<Self> {
+        check_fraction(fraction)?;
+        Ok(Self {
+            r1,
+            r2,
+            fraction,
+            rng: SplitMix64(seed),
+        })
+    }
+
+    /// Returns the next sampled pair of records.
+    ///
+    /// Returns an error if one input ends before the other.
+    pub fn next_pair(&mut self) -> Result<Option<(Record, Record)>> {
+        while let Some(pair) = next_pair(&mut self.r1, &mut self.r2)? {
+            if self.rng.next_f64() < self.fraction {
+                return Ok(Some(pair));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R1: FastxRead, R2: FastxRead> Iterator for PairedFractionSampler<R1, R2> {
+    type Item = (Record, Record);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_pair() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+/// # Usage
+/// Selects exactly `n` pairs of records from paired inputs (or every
+/// pair if there are fewer) with reservoir sampling.
+///
+/// Mates are always kept together and returned in their input order.
+/// Returns an error if one input ends before the other.
+/// ```
+/// use fxread::{initialize_reader, paired_reservoir_sample};
+///
+/// let r1 = initialize_reader("example/sequences.fq").unwrap();
+/// let r2 = initialize_reader("example/sequences.fq.zst").unwrap();
+/// let sample = paired_reservoir_sample(r1, r2, 4, 11).unwrap();
+/// assert_eq!(sample.len(), 4);
+/// assert!(sample.iter().all(|(a, b)| a.id() == b.id()));
+/// ```
+pub fn paired_reservoir_sample<R1, R2>(
+    mut r1: R1,
+    mut r2: R2,
+    n: usize,
+    seed: u64,
+) -> Result<Vec<(Record, Record)>>
+where
+    R1: FastxRead,
+    R2: FastxRead,
+{
+    let mut rng = SplitMix64(seed);
+    let mut reservoir = Vec::new();
+    let mut seen = 0;
+    while let Some(pair) = next_pair(&mut r1, &mut r2)? {
+        reserve(&mut reservoir, &mut rng, n, seen, pair);
+        seen += 1;
+    }
+    Ok(ordered(reservoir))
+}
+
+/// Offers the item at position `seen` to a reservoir of size `n`
+fn reserve<T>(
+    reservoir: &mut Vec<(usize, T)>,
+    rng: &mut SplitMix64,
+    n: usize,
+    seen: usize,
+    item: T,
+) {
+    if reservoir.len() < n {
+        reservoir.push((seen, item));
+    } else {
+        let idx = rng.below(seen as u64 + 1) as usize;
+        if idx < n {
+            reservoir[idx] = (seen, item);
+        }
+    }
+}
+
+/// Restores the input order of a reservoir
+fn ordered<T>(mut reservoir: Vec<(usize, T)>) -> Vec<T> {
+    reservoir.sort_unstable_by_key(|(idx, _)| *idx);
+    reservoir.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        paired_reservoir_sample, reservoir_sample, FractionSampler, PairedFractionSampler,
+        SplitMix64,
+    };
+    use crate::{FastaReader, FastxRead};
+    use std::io::Cursor;
+
+    fn reader(n: usize) -> FastaReader<Cursor<Vec<u8>>> {
+        let mut data = Vec::new();
+        for idx in 0..n {
+            data.extend_from_slice(format!(">seq.{}\nACGT\n", idx).as_bytes());
+        }
+        FastaReader::new(Cursor::new(data))
+    }
+
+    fn ids(records: &[crate::Record]) -> Vec<String> {
+        records.iter().map(|r| r.id_str().to_string()).collect()
+    }
+
+    #[test]
+    fn rng_is_uniform() {
+        let mut rng = SplitMix64(0);
+        let mut buckets = [0; 10];
+        for _ in 0..100_000 {
+            buckets[rng.below(10) as usize] += 1;
+        }
+        assert!(buckets.iter().all(|b| (9_000..11_000).contains(b)));
+        assert!((0..1000).all(|_| (0.0..1.0).contains(&rng.next_f64())));
+    }
+
+    #[test]
+    fn fraction_is_reproducible() {
+        let a: Vec<_> = FractionSampler::new(reader(1000), 0.3, 1)
+            .unwrap()
+            .collect();
+        let b: Vec<_> = FractionSampler::new(reader(1000), 0.3, 1)
+            .unwrap()
+            .collect();
+        let c: Vec<_> = FractionSampler::new(reader(1000), 0.3, 2)
+            .unwrap()
+            .collect();
+        assert_eq!(ids(&a), ids(&b));
+        assert_ne!(ids(&a), ids(&c));
+    }
+
+    #[test]
+    fn fraction_size() {
+        let sampled = FractionSampler::new(reader(10_000), 0.25, 5)
+            .unwrap()
+            .count();
+        assert!((2_300..2_700).contains(&sampled));
+        assert_eq!(FractionSampler::new(reader(50), 0.0, 5).unwrap().count(), 0);
+        assert_eq!(
+            FractionSampler::new(reader(50), 1.0, 5).unwrap().count(),
+            50
+        );
+    }
+
+    #[test]
+    fn invalid_fraction() {
+        assert!(FractionSampler::new(reader(1), 1.5, 0).is_err());
+        assert!(FractionSampler::new(reader(1), -0.1, 0).is_err());
+        assert!(FractionSampler::new(reader(1), f64::NAN, 0).is_err());
+    }
+
+    #[test]
+    fn sampler_format() {
+        let sampler = FractionSampler::new(reader(1), 0.5, 0).unwrap();
+        assert_eq!(sampler.format(), Some(crate::Format::Fasta));
+    }
+
+    #[test]
+    fn reservoir_exact_and_ordered() {
+        let sample = reservoir_sample(reader(1000), 25, 9).unwrap();
+        assert_eq!(sample.len(), 25);
+        let positions: Vec<usize> = sample
+            .iter()
+            .map(|r| r.id_str()[4..].parse().unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        let again = reservoir_sample(reader(1000), 25, 9).unwrap();
+        assert_eq!(ids(&sample), ids(&again));
+    }
+
+    #[test]
+    fn reservoir_is_uniform() {
+        let mut late = 0;
+        for seed in 0..200 {
+            let sample = reservoir_sample(reader(100), 10, seed).unwrap();
+            late += sample
+                .iter()
+                .filter(|r| r.id_str()[4..].parse::<usize>().unwrap() >= 50)
+                .count();
+        }
+        assert!((850..1150).contains(&late));
+    }
+
+    #[test]
+    fn reservoir_smaller_input() {
+        let sample = reservoir_sample(reader(5), 10, 0).unwrap();
+        assert_eq!(ids(&sample), ["seq.0", "seq.1", "seq.2", "seq.3", "seq.4"]);
+        assert!(reservoir_sample(reader(5), 0, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn paired_mates_together() {
+        let pairs: Vec<_> = PairedFractionSampler::new(reader(500), reader(500), 0.2, 4)
+            .unwrap()
+            .collect();
+        assert!(!pairs.is_empty());
+        assert!(pairs.iter().all(|(a, b)| a.id() == b.id()));
+
+        let pairs = paired_reservoir_sample(reader(500), reader(500), 20, 4).unwrap();
+        assert_eq!(pairs.len(), 20);
+        assert!(pairs.iter().all(|(a, b)| a.id() == b.id()));
+    }
+
+    #[test]
+    fn paired_length_mismatch() {
+        assert!(paired_reservoir_sample(reader(5), reader(4), 2, 0).is_err());
+        let mut sampler = PairedFractionSampler::new(reader(4), reader(5), 1.0, 0).unwrap();
+        for _ in 0..4 {
+            sampler.next_pair().unwrap();
+        }
+        assert!(sampler.next_pair().is_err());
+    }
+}
@@ -0,0 +1,623 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use anyhow::{bail, Result};
+#[cfg(feature = "regex")]
+use regex::bytes::Regex;
+use std::{collections::HashSet, fmt};
+
+/// Returns the first whitespace delimited token of a header
+pub(crate) fn header_id(header: &[u8]) -> &[u8] {
+    header
+        .split(|b| b.is_ascii_whitespace())
+        .next()
+        .unwrap_or(header)
+}
+
+/// Converts a glob into an anchored regular expression.
+///
+/// Supports `*`, `?` and bracketed character classes (negated with `!`).
+#[cfg(feature = "regex")]
+fn glob_to_regex(glob: &str) -> Result<String> {
+    let mut regex = String::from("^(?-u:");
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                let mut first = true;
+                loop {
+                    match chars.next() {
+                        Some(']') if !first => break,
+                        Some('!') if first => regex.push('^'),
+                        Some(c @ ('\\' | '[' | '&' | '~')) => {
+                            regex.push('\\');
+                            regex.push(c);
+                        }
+                        Some(c) => regex.push(c),
+                        None => bail!("Unclosed character class in glob: {}", glob),
+                    }
+                    first = false;
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    regex.push_str(")$");
+    Ok(regex)
+}
+
+/// The ways a [`HeaderPattern`] can match
+#[derive(Debug, Clone)]
+enum HeaderKind {
+    Ids(HashSet<Vec<u8>>),
+    #[cfg(feature = "regex")]
+    Regex(Regex),
+    #[cfg(feature = "regex")]
+    Glob(Regex),
+}
+
+/// A pattern matched against record headers.
+///
+/// ID sets and globs are compared against the record ID (the header up
+/// to the first whitespace) while regular expressions search the full
+/// header.
+#[derive(Debug, Clone)]
+pub struct HeaderPattern {
+    kind: HeaderKind,
+}
+impl HeaderPattern {
+    /// # Usage
+    /// Matches records whose ID is one of `ids`
+    /// ```
+    /// use fxread::HeaderPattern;
+    ///
+    /// let pattern = HeaderPattern::ids(["seq.0", "seq.2"]);
+    /// assert!(pattern.is_match(b"seq.0 length=4"));
+    /// assert!(!pattern.is_match(b"seq.1"));
+    /// ```
+    pub fn ids<I, T>(ids: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let ids = ids.into_iter().map(|id| id.as_ref().to_vec()).collect();
+        Self {
+            kind: HeaderKind::Ids(ids),
+        }
+    }
+
+    /// # Usage
+    /// Matches records whose header contains a match of the regular
+    /// expression
+    /// ```
+    /// use fxread::HeaderPattern;
+    ///
+    /// let pattern = HeaderPattern::regex(r"sample=(A|B)\b").unwrap();
+    /// assert!(pattern.is_match(b"seq.0 sample=B"));
+    /// assert!(HeaderPattern::regex("(").is_err());
+    /// ```
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Ok(Self {
+            kind: HeaderKind::Regex(Regex::new(pattern)?),
+        })
+    }
+
+    /// # Usage
+    /// Matches records whose whole ID matches the glob
+    /// ```
+    /// use fxread::HeaderPattern;
+    ///
+    /// let pattern = HeaderPattern::glob("seq.[0-4]*").unwrap();
+    /// assert!(pattern.is_match(b"seq.12 sample=A"));
+    /// assert!(!pattern.is_match(b"seq.52"));
+    /// ```
+    ///
+    /// Requires the `regex` feature.
+    #[cfg(feature = "regex")]
+    pub fn glob(pattern: &str) -> Result<Self> {
+        Ok(Self {
+            kind: HeaderKind::Glob(Regex::new(&glob_to_regex(pattern)?)?),
+        })
+    }
+
+    /// Checks whether a header (without its marker) matches the pattern
+    #[must_use]
+    pub fn is_match(&self, header: &[u8]) -> bool {
+        match &self.kind {
+            HeaderKind::Ids(ids) => ids.contains(header_id(header)),
+            #[cfg(feature = "regex")]
+            HeaderKind::Regex(regex) => regex.is_match(header),
+            #[cfg(feature = "regex")]
+            HeaderKind::Glob(regex) => regex.is_match(header_id(header)),
+        }
+    }
+}
+
+/// The strand a [`Match`] was found on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strand {
+    /// The motif as given
+    Forward,
+    /// The reverse complement of the motif
+    Reverse,
+}
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strand::Forward => write!(f, "+"),
+            Strand::Reverse => write!(f, "-"),
+        }
+    }
+}
+
+/// The location of a [`Motif`] within a sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    start: usize,
+    end: usize,
+    strand: Strand,
+    mismatches: usize,
+}
+impl Match {
+    /// Returns the 0-based start of the match on the forward strand
+    #[must_use]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the exclusive end of the match on the forward strand
+    #[must_use]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns the strand the motif was found on
+    #[must_use]
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// Returns the number of mismatched bases
+    #[must_use]
+    pub fn mismatches(&self) -> usize {
+        self.mismatches
+    }
+}
+
+/// Bit masks of the nucleotides for A, C, G and T
+const A: u8 = 1;
+const C: u8 = 2;
+const G: u8 = 4;
+const T: u8 = 8;
+
+/// Returns the nucleotide mask of a sequence base (0 for non-ACGTU)
+fn base_mask(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'A' => A,
+        b'C' => C,
+        b'G' => G,
+        b'T' | b'U' => T,
+        _ => 0,
+    }
+}
+
+/// Returns the nucleotide mask of an IUPAC code
+fn iupac_mask(code: u8) -> u8 {
+    match code.to_ascii_uppercase() {
+        b'R' => A | G,
+        b'Y' => C | T,
+        b'S' => C | G,
+        b'W' => A | T,
+        b'K' => G | T,
+        b'M' => A | C,
+        b'B' => C | G | T,
+        b'D' => A | G | T,
+        b'H' => A | C | T,
+        b'V' => A | C | G,
+        b'N' => A | C | G | T,
+        c => base_mask(c),
+    }
+}
+
+/// Complements a nucleotide mask by swapping A/T and C/G
+fn complement_mask(mask: u8) -> u8 {
+    (mask & A) << 3 | (mask & C) << 1 | (mask & G) >> 1 | (mask & T) >> 3
+}
+
+/// A nucleotide motif searched for within sequences.
+///
+/// Each position of the motif is a set of accepted nucleotides so that
+/// exact and degenerate motifs share the same search. Sequence bases
+/// outside of ACGTU (e.g. 'N') never match. Overlapping matches are all
+/// reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Motif {
+    forward: Vec<u8>,
+    reverse: Vec<u8>,
+    mismatches: usize,
+    both_strands: bool,
+}
+impl Motif {
+    /// Builds a motif from masks rejecting any zero mask
+    fn from_masks(motif: &[u8], mask: fn(u8) -> u8) -> Result<Self> {
+        if motif.is_empty() {
+            bail!("Search motif must not be empty");
+        }
+        let forward = motif
+            .iter()
+            .map(|b| match mask(*b) {
+                0 => bail!("Invalid character in search motif: '{}'", char::from(*b)),
+                m => Ok(m),
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        let reverse = forward.iter().rev().map(|m| complement_mask(*m)).collect();
+        Ok(Self {
+            forward,
+            reverse,
+            mismatches: 0,
+            both_strands: false,
+        })
+    }
+
+    /// # Usage
+    /// Creates a motif of the nucleotides ACGTU (case-insensitive)
+    /// ```
+    /// use fxread::Motif;
+    ///
+    /// let motif = Motif::exact(b"GATC").unwrap();
+    /// let matches = motif.find_all(b"AAGATCGATC");
+    /// assert_eq!(matches.len(), 2);
+    /// assert_eq!(matches[1].start(), 6);
+    /// assert!(Motif::exact(b"GANC").is_err());
+    /// ```
+    pub fn exact(motif: &[u8]) -> Result<Self> {
+        Self::from_masks(motif, base_mask)
+    }
+
+    /// # Usage
+    /// Creates a motif which may contain IUPAC degenerate codes
+    /// ```
+    /// use fxread::Motif;
+    ///
+    /// let motif = Motif::iupac(b"GANTC").unwrap();
+    /// assert!(motif.is_match(b"TTGAATCTT"));
+    /// assert!(!motif.is_match(b"TTGAATGTT"));
+    /// ```
+    pub fn iupac(motif: &[u8]) -> Result<Self> {
+        Self::from_masks(motif, iupac_mask)
+    }
+
+    /// Sets the maximum number of mismatched bases in a match
+    #[must_use]
+    pub fn mismatches(mut self, mismatches: usize) -> Self {
+        self.mismatches = mismatches;
+        self
+    }
+
+    /// Sets whether the reverse complement of the motif is also searched
+    #[must_use]
+    pub fn both_strands(mut self, both_strands: bool) -> Self {
+        self.both_strands = both_strands;
+        self
+    }
+
+    /// Returns the length of the motif
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    /// Returns whether the motif is empty (never true)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    /// Returns the number of mismatches of `masks` against `window` if
+    /// it does not exceed the limit
+    fn mismatches_at(&self, masks: &[u8], window: &[u8]) -> Option<usize> {
+        let mut mismatches = 0;
+        for (mask, base) in masks.iter().zip(window) {
+            if mask & base_mask(*base) == 0 {
+                mismatches += 1;
+                if mismatches > self.mismatches {
+                    return None;
+                }
+            }
+        }
+        Some(mismatches)
+    }
+
+    /// # Usage
+    /// Finds all matches of the motif in a sequence ordered by their
+    /// start position (forward before reverse at the same position).
+    ///
+    /// Palindromic motifs are only reported on the forward strand.
+    /// ```
+    /// use fxread::{Motif, Strand};
+    ///
+    /// let motif = Motif::exact(b"AACG").unwrap().mismatches(1).both_strands(true);
+    /// let matches = motif.find_all(b"TTCGTTAACC");
+    /// assert_eq!(matches.len(), 2);
+    /// assert_eq!(matches[0].strand(), Strand::Reverse);
+    /// assert_eq!((matches[0].start(), matches[0].end()), (2, 6));
+    /// assert_eq!(matches[1].mismatches(), 1);
+    /// ```
+    #[must_use]
+    pub fn find_all(&self, seq: &[u8]) -> Vec<Match> {
+        let search_reverse = self.both_strands && self.reverse != self.forward;
+        let mut matches = Vec::new();
+        for (start, window) in seq.windows(self.len()).enumerate() {
+            let strands = [
+                (Strand::Forward, &self.forward, true),
+                (Strand::Reverse, &self.reverse, search_reverse),
+            ];
+            for (strand, masks, search) in strands {
+                if !search {
+                    continue;
+                }
+                if let Some(mismatches) = self.mismatches_at(masks, window) {
+                    matches.push(Match {
+                        start,
+                        end: start + self.len(),
+                        strand,
+                        mismatches,
+                    });
+                }
+            }
+        }
+        matches
+    }
+
+    /// Checks whether the motif occurs in a sequence
+    #[must_use]
+    pub fn is_match(&self, seq: &[u8]) -> bool {
+        let search_reverse = self.both_strands && self.reverse != self.forward;
+        seq.windows(self.len()).any(|window| {
+            self.mismatches_at(&self.forward, window).is_some()
+                || (search_reverse && self.mismatches_at(&self.reverse, window).is_some())
+        })
+    }
+}
+
+/// A record matching a [`SearchReader`] with the positions of its
+/// sequence matches.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    record: Record,
+    matches: Vec<Match>,
+}
+impl Hit {
+    /// Returns the matching record
+    #[must_use]
+    pub fn record(&self) -> &Record {
+        &self.record
+    }
+
+    /// Returns the sequence matches (empty without a [`Motif`])
+    #[must_use]
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    /// Consumes the hit and returns its record
+    #[must_use]
+    pub fn into_record(self) -> Record {
+        self.record
+    }
+}
+
+/// A reader adaptor which only yields records matching all of its
+/// header and sequence patterns.
+///
+/// ```
+/// use fxread::{initialize_reader, HeaderPattern, Motif, SearchReader};
+///
+/// let reader = initialize_reader("example/sequences.fa").unwrap();
+/// let mut search = SearchReader::new(reader)
+///     .header(HeaderPattern::ids(["seq.0", "seq.1", "seq.2"]))
+///     .sequence(Motif::iupac(b"GAWTC").unwrap().both_strands(true));
+/// while let Some(hit) = search.next_hit().unwrap() {
+///     for m in hit.matches() {
+///         println!("{}\t{}\t{}\t{}", hit.record().id_str(), m.start(), m.end(), m.strand());
+///     }
+/// }
+/// ```
+pub struct SearchReader<R: FastxRead> {
+    reader: R,
+    headers: Vec<HeaderPattern>,
+    motifs: Vec<Motif>,
+}
+impl<R: FastxRead> SearchReader<R> {
+    /// Creates a new [`SearchReader`] which matches every record
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            headers: Vec::new(),
+            motifs: Vec::new(),
+        }
+    }
+
+    /// Adds a [`HeaderPattern`] which headers must match
+    #[must_use]
+    pub fn header(mut self, pattern: HeaderPattern) -> Self {
+        self.headers.push(pattern);
+        self
+    }
+
+    /// Adds a [`Motif`] which sequences must contain
+    #[must_use]
+    pub fn sequence(mut self, motif: Motif) -> Self {
+        self.motifs.push(motif);
+        self
+    }
+
+    /// Returns the next matching record with its sequence matches
+    pub fn next_hit(&mut self) -> Result<Option<Hit>> {
+        'records: while let Some(record) = self.reader.next_record()? {
+            if !self.headers.iter().all(|p| p.is_match(record.id())) {
+                continue;
+            }
+            let mut matches = Vec::new();
+            for motif in &self.motifs {
+                let found = motif.find_all(record.seq());
+                if found.is_empty() {
+                    continue 'records;
+                }
+                matches.extend(found);
+            }
+            return Ok(Some(Hit { record, matches }));
+        }
+        Ok(None)
+    }
+}
+
+impl<R: FastxRead> FastxRead for SearchReader<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        Ok(self.next_hit()?.map(Hit::into_record))
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for SearchReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "regex")]
+    use super::glob_to_regex;
+    use super::{complement_mask, HeaderPattern, Motif, SearchReader, Strand};
+    use crate::FastaReader;
+
+    #[test]
+    fn header_ids() {
+        let pattern = HeaderPattern::ids(vec![b"a.1".to_vec()]);
+        assert!(pattern.is_match(b"a.1"));
+        assert!(pattern.is_match(b"a.1\tdescription"));
+        assert!(!pattern.is_match(b"a.10"));
+        assert!(!pattern.is_match(b""));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn header_globs() {
+        assert_eq!(glob_to_regex("a*.?").unwrap(), r"^(?-u:a.*\..)$");
+        let pattern = HeaderPattern::glob("read_[!0-2]").unwrap();
+        assert!(pattern.is_match(b"read_5 x"));
+        assert!(!pattern.is_match(b"read_1"));
+        assert!(!pattern.is_match(b"read_55"));
+        let pattern = HeaderPattern::glob("a[]]b").unwrap();
+        assert!(pattern.is_match(b"a]b"));
+        assert!(HeaderPattern::glob("a[b").is_err());
+    }
+
+    #[test]
+    fn complement_masks() {
+        assert_eq!(complement_mask(1), 8);
+        assert_eq!(complement_mask(2), 4);
+        assert_eq!(complement_mask(1 | 4), 8 | 2);
+        assert_eq!(complement_mask(15), 15);
+    }
+
+    #[test]
+    fn exact_motif() {
+        let motif = Motif::exact(b"acg").unwrap();
+        let starts: Vec<_> = motif
+            .find_all(b"ACGACGNACG")
+            .iter()
+            .map(|m| m.start())
+            .collect();
+        assert_eq!(starts, [0, 3, 7]);
+        assert!(motif.find_all(b"AC").is_empty());
+        assert!(Motif::exact(b"").is_err());
+    }
+
+    #[test]
+    fn rna_and_n_bases() {
+        let motif = Motif::iupac(b"UNA").unwrap();
+        assert!(motif.is_match(b"TGA"));
+        assert!(!motif.is_match(b"TNA"));
+    }
+
+    #[test]
+    fn mismatches() {
+        let motif = Motif::exact(b"AAAA").unwrap().mismatches(1);
+        let found: Vec<_> = motif
+            .find_all(b"AATAAA")
+            .iter()
+            .map(|m| (m.start(), m.mismatches()))
+            .collect();
+        assert_eq!(found, [(0, 1), (1, 1), (2, 1)]);
+        assert!(!Motif::exact(b"AAAA").unwrap().is_match(b"AATAAA"));
+    }
+
+    #[test]
+    fn reverse_strand() {
+        let motif = Motif::iupac(b"GGR").unwrap();
+        assert!(!motif.is_match(b"TTCCT"));
+        let motif = motif.both_strands(true);
+        let matches = motif.find_all(b"TTCCT");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].strand(), Strand::Reverse);
+        assert_eq!(matches[0].start(), 1);
+        assert_eq!(matches[0].strand().to_string(), "-");
+    }
+
+    #[test]
+    fn palindrome_reported_once() {
+        let motif = Motif::exact(b"GAATTC").unwrap().both_strands(true);
+        let matches = motif.find_all(b"AGAATTCA");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].strand(), Strand::Forward);
+    }
+
+    const FASTA: &[u8] = b">a.0 x\nACGT\n>a.1 y\nGGGG\n>b.0 x\nACGT\n>a.2 x\nTTTT\n";
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn search_reader_patterns() {
+        let mut search = SearchReader::new(FastaReader::new(FASTA))
+            .header(HeaderPattern::glob("a.*").unwrap())
+            .header(HeaderPattern::regex(" x$").unwrap())
+            .sequence(Motif::exact(b"CG").unwrap());
+        let hit = search.next_hit().unwrap().unwrap();
+        assert_eq!(hit.record().id_str(), "a.0 x");
+        assert_eq!(hit.matches()[0].start(), 1);
+        assert!(search.next_hit().unwrap().is_none());
+    }
+
+    #[test]
+    fn search_reader() {
+        let fasta = FASTA;
+        let mut search = SearchReader::new(FastaReader::new(fasta))
+            .header(HeaderPattern::ids(["a.0", "a.1", "a.2"]))
+            .header(HeaderPattern::ids(["a.0", "a.2", "b.0"]))
+            .sequence(Motif::exact(b"CG").unwrap());
+        let hit = search.next_hit().unwrap().unwrap();
+        assert_eq!(hit.record().id_str(), "a.0 x");
+        assert_eq!(hit.matches()[0].start(), 1);
+        assert!(search.next_hit().unwrap().is_none());
+
+        let search = SearchReader::new(FastaReader::new(fasta))
+            .sequence(Motif::exact(b"AAAA").unwrap().both_strands(true));
+        let ids: Vec<_> = search.map(|r| r.id_str().to_string()).collect();
+        assert_eq!(ids, ["a.2 x"]);
+    }
+}
@@ -0,0 +1,403 @@
+use super::fastx::FastxRead;
+use super::record::Record;
+use super::search::header_id;
+use super::{FastxWriter, ReaderBuilder};
+use anyhow::Result;
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+/// The default memory budget of an [`ExternalSorter`] (256 MiB)
+const DEFAULT_MEMORY: usize = 256 * 1024 * 1024;
+
+/// The maximum number of runs merged at once
+const MAX_FAN_IN: usize = 64;
+
+/// Distinguishes the runs of sorters within the same process
+static SORT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The writer of a spilled run
+type RunWriter = FastxWriter<GzEncoder<BufWriter<File>>>;
+
+/// The value records are ordered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// The record ID (the header up to the first whitespace)
+    Id,
+    /// The sequence bytes
+    Sequence,
+    /// The sequence length
+    Length,
+}
+impl SortKey {
+    /// Compares two records by the key in ascending order
+    fn compare(self, a: &Record, b: &Record) -> Ordering {
+        match self {
+            SortKey::Id => header_id(a.id()).cmp(header_id(b.id())),
+            SortKey::Sequence => a.seq().cmp(b.seq()),
+            SortKey::Length => a.seq().len().cmp(&b.seq().len()),
+        }
+    }
+}
+
+/// A sorted run spilled to a temporary file which is removed on drop
+struct Run {
+    path: PathBuf,
+}
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The next record of a run being merged.
+///
+/// Heads are ordered so that the max-heap [`BinaryHeap`] pops the
+/// smallest record first and, among equal records, the one of the
+/// earliest run, which keeps the merge stable.
+struct Head {
+    record: Record,
+    run: usize,
+    key: SortKey,
+    reverse: bool,
+}
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut ordering = self.key.compare(&other.record, &self.record);
+        if self.reverse {
+            ordering = ordering.reverse();
+        }
+        ordering.then(other.run.cmp(&self.run))
+    }
+}
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Head {}
+
+/// Sorts records which may not fit in memory.
+///
+/// Records are collected until the memory budget is reached, sorted,
+/// and spilled to gzip compressed runs in the temporary directory which
+/// are then merged into the output, at most 64 at a time. The sort is
+/// stable so records with equal keys keep their input order.
+///
+/// ```
+/// use fxread::{initialize_reader, ExternalSorter, FastxWriter, SortKey};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let mut writer = FastxWriter::new(Vec::new());
+/// let sorted = ExternalSorter::new(SortKey::Length)
+///     .reverse(true)
+///     .memory(1024)
+///     .sort(reader, &mut writer)
+///     .unwrap();
+/// assert_eq!(sorted, 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExternalSorter {
+    key: SortKey,
+    reverse: bool,
+    memory: usize,
+    temp_dir: PathBuf,
+}
+impl ExternalSorter {
+    /// Creates a new [`ExternalSorter`] ordering records by `key` with a
+    /// 256 MiB memory budget and the system temporary directory
+    #[must_use]
+    pub fn new(key: SortKey) -> Self {
+        Self {
+            key,
+            reverse: false,
+            memory: DEFAULT_MEMORY,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Sets whether records are sorted in descending order
+    #[must_use]
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Sets the approximate number of bytes of records held in memory
+    /// before a run is spilled to disk
+    ///
+    /// # Panics
+    /// Panics if `bytes` is zero
+    #[must_use]
+    pub fn memory(mut self, bytes: usize) -> Self {
+        assert!(bytes > 0, "memory budget must be greater than zero");
+        self.memory = bytes;
+        self
+    }
+
+    /// Sets the directory spilled runs are written to
+    #[must_use]
+    pub fn temp_dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.temp_dir = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Compares two records by the sort key
+    fn compare(&self, a: &Record, b: &Record) -> Ordering {
+        let ordering = self.key.compare(a, b);
+        if self.reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Creates the `n`th run of the sort `id` and its writer
+    fn create_run(&self, id: usize, n: usize) -> Result<(Run, RunWriter)> {
+        let run = Run {
+            path: self.temp_dir.join(format!(
+                "fxread-sort-{}-{}-{}.gz",
+                std::process::id(),
+                id,
+                n
+            )),
+        };
+        let file = BufWriter::new(File::create(&run.path)?);
+        let writer = FastxWriter::new(GzEncoder::new(file, Compression::fast()));
+        Ok((run, writer))
+    }
+
+    /// Writes the gzip trailer of a run and flushes it to disk
+    fn close_run(writer: RunWriter) -> Result<()> {
+        writer.into_inner().finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Sorts a chunk of records and writes it to the `n`th run
+    fn spill(&self, records: &mut Vec<Record>, id: usize, n: usize) -> Result<Run> {
+        records.sort_by(|a, b| self.compare(a, b));
+        let (run, mut writer) = self.create_run(id, n)?;
+        writer.write_all(records.drain(..))?;
+        Self::close_run(writer)?;
+        Ok(run)
+    }
+
+    /// Merges sorted runs into `writer` and returns the number of
+    /// records written
+    fn merge<W: Write>(&self, runs: &[Run], writer: &mut FastxWriter<W>) -> Result<u64> {
+        let mut readers = runs
+            .iter()
+            .map(|run| ReaderBuilder::new().open(&run.path))
+            .collect::<Result<Vec<_>>>()?;
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(record) = reader.next_record()? {
+                heap.push(self.head(record, run));
+            }
+        }
+        let mut count = 0;
+        while let Some(head) = heap.pop() {
+            writer.write_record(&head.record)?;
+            count += 1;
+            if let Some(record) = readers[head.run].next_record()? {
+                heap.push(self.head(record, head.run));
+            }
+        }
+        Ok(count)
+    }
+
+    /// Wraps the next record of a run for the merge heap
+    fn head(&self, record: Record, run: usize) -> Head {
+        Head {
+            record,
+            run,
+            key: self.key,
+            reverse: self.reverse,
+        }
+    }
+
+    /// # Usage
+    /// Sorts all records of `reader` into `writer` and returns the
+    /// number of records written.
+    ///
+    /// Inputs fitting within the memory budget are sorted without
+    /// touching the disk.
+    /// ```
+    /// use fxread::{ExternalSorter, FastaReader, FastxWriter, SortKey};
+    ///
+    /// let input: &'static [u8] = b">b\nAC\n>a\nA\n>c\nA\n";
+    /// let mut writer = FastxWriter::new(Vec::new());
+    /// ExternalSorter::new(SortKey::Id)
+    ///     .sort(FastaReader::new(input), &mut writer)
+    ///     .unwrap();
+    /// assert_eq!(writer.into_inner(), b">a\nA\n>b\nAC\n>c\nA\n");
+    /// ```
+    pub fn sort<R, W>(&self, mut reader: R, writer: &mut FastxWriter<W>) -> Result<u64>
+    where
+        R: FastxRead,
+        W: Write,
+    {
+        let id = SORT_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        let mut created = 0;
+        let mut runs = Vec::new();
+        let mut records = Vec::new();
+        let mut used = 0;
+        while let Some(record) = reader.next_record()? {
+            used += record.data().len() + std::mem::size_of::<Record>();
+            records.push(record);
+            if used >= self.memory {
+                runs.push(self.spill(&mut records, id, created)?);
+                created += 1;
+                used = 0;
+            }
+        }
+
+        // everything fit in memory
+        if runs.is_empty() {
+            records.sort_by(|a, b| self.compare(a, b));
+            let count = records.len() as u64;
+            writer.write_all(records)?;
+            return Ok(count);
+        }
+        if !records.is_empty() {
+            runs.push(self.spill(&mut records, id, created)?);
+            created += 1;
+        }
+
+        // merge consecutive groups of runs until they can be opened at once
+        while runs.len() > MAX_FAN_IN {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(MAX_FAN_IN));
+            let mut rest = runs.into_iter().peekable();
+            while rest.peek().is_some() {
+                let group: Vec<Run> = rest.by_ref().take(MAX_FAN_IN).collect();
+                if group.len() == 1 {
+                    merged.extend(group);
+                    continue;
+                }
+                let (run, mut run_writer) = self.create_run(id, created)?;
+                created += 1;
+                self.merge(&group, &mut run_writer)?;
+                Self::close_run(run_writer)?;
+                merged.push(run);
+            }
+            runs = merged;
+        }
+        self.merge(&runs, writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExternalSorter, SortKey};
+    use crate::{FastaReader, FastqReader, FastxWriter, Record};
+    use std::path::PathBuf;
+
+    fn sort(sorter: &ExternalSorter, input: &'static [u8]) -> Vec<String> {
+        let mut writer = FastxWriter::new(Vec::new());
+        sorter.sort(FastaReader::new(input), &mut writer).unwrap();
+        FastaReader::new(std::io::Cursor::new(writer.into_inner()))
+            .map(|r: Record| r.id_str().to_string())
+            .collect()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("fxread-sort-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    const FASTA: &[u8] = b">d 1\nACGTA\n>b\nTT\n>a\nGGG\n>c\nA\n>b 2\nCCCC\n>e\nTT\n";
+
+    #[test]
+    fn in_memory() {
+        let sorter = ExternalSorter::new(SortKey::Id);
+        assert_eq!(sort(&sorter, FASTA), ["a", "b", "b 2", "c", "d 1", "e"]);
+        let sorter = ExternalSorter::new(SortKey::Sequence);
+        assert_eq!(sort(&sorter, FASTA), ["c", "d 1", "b 2", "a", "b", "e"]);
+    }
+
+    #[test]
+    fn spilled_runs() {
+        let dir = temp_dir("spill");
+        for memory in [1, 200, 400] {
+            let sorter = ExternalSorter::new(SortKey::Length)
+                .memory(memory)
+                .temp_dir(&dir);
+            assert_eq!(sort(&sorter, FASTA), ["c", "b", "e", "a", "b 2", "d 1"]);
+            let sorter = sorter.reverse(true);
+            assert_eq!(sort(&sorter, FASTA), ["d 1", "b 2", "a", "b", "e", "c"]);
+        }
+        // runs are removed once merged
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn multi_pass_merge() {
+        // more runs than can be merged at once
+        let input: Vec<u8> = (0..300)
+            .flat_map(|i| format!(">r{}\n{}\n", i, "A".repeat(1 + (i * 7) % 5)).into_bytes())
+            .collect();
+        let dir = temp_dir("passes");
+        let mut writer = FastxWriter::new(Vec::new());
+        let count = ExternalSorter::new(SortKey::Length)
+            .memory(1)
+            .temp_dir(&dir)
+            .sort(FastaReader::new(std::io::Cursor::new(input)), &mut writer)
+            .unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+        assert_eq!(count, 300);
+
+        let records: Vec<Record> =
+            FastaReader::new(std::io::Cursor::new(writer.into_inner())).collect();
+        assert_eq!(records.len(), 300);
+        for pair in records.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            let index = |r: &Record| r.id_str()[1..].parse::<usize>().unwrap();
+            // ascending lengths, input order within a length
+            assert!((a.seq().len(), index(a)) < (b.seq().len(), index(b)));
+        }
+    }
+
+    #[test]
+    fn fastq_roundtrip() {
+        let input: &'static [u8] = b"@r2\nAC\n+\nII\n@r1\nA\n+\n#\n@r3\nACG\n+\n!!!\n";
+        let dir = temp_dir("fastq");
+        let mut writer = FastxWriter::new(Vec::new());
+        let count = ExternalSorter::new(SortKey::Id)
+            .memory(1)
+            .temp_dir(&dir)
+            .sort(FastqReader::new(input), &mut writer)
+            .unwrap();
+        std::fs::remove_dir(&dir).unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            writer.into_inner(),
+            b"@r1\nA\n+\n#\n@r2\nAC\n+\nII\n@r3\nACG\n+\n!!!\n"
+        );
+    }
+
+    #[test]
+    fn empty_input() {
+        let mut writer = FastxWriter::new(Vec::new());
+        let input: &'static [u8] = b"";
+        let count = ExternalSorter::new(SortKey::Id)
+            .sort(FastaReader::new(input), &mut writer)
+            .unwrap();
+        assert_eq!(count, 0);
+        assert!(writer.into_inner().is_empty());
+    }
+}
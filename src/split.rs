@@ -0,0 +1,421 @@
+use super::count::Counts;
+use super::fastx::FastxRead;
+use super::record::Record;
+use super::sample::next_pair;
+use super::FastxWriter;
+use anyhow::{bail, Result};
+use bzip2::write::BzEncoder;
+use flate2::write::GzEncoder;
+use liblzma::write::XzEncoder;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The compression level of written parts
+const LEVEL: u32 = 6;
+
+/// An output file compressed according to its extension.
+///
+/// Compressed formats write their trailer when finished, so writers
+/// must be closed with [`Encoder::finish`] for errors to be reported.
+pub(crate) enum Encoder {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Bzip(BzEncoder<BufWriter<File>>),
+    Xz(XzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+impl Encoder {
+    /// Creates a file compressed according to the extension of its path
+    /// (`.gz`, `.bz2`, `.xz` or `.zst`)
+    ///
+    /// Returns an error for `.bgz` paths since blocked gzip is not written.
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        if extension == Some("bgz") {
+            bail!(
+                "Writing BGZF (.bgz) files is not supported: {}",
+                path.display()
+            );
+        }
+        let file = BufWriter::new(File::create(path)?);
+        let encoder = match extension {
+            Some("gz") => Encoder::Gzip(GzEncoder::new(file, flate2::Compression::new(LEVEL))),
+            Some("bz2") => Encoder::Bzip(BzEncoder::new(file, bzip2::Compression::new(LEVEL))),
+            Some("xz") => Encoder::Xz(XzEncoder::new(file, LEVEL)),
+            Some("zst") => Encoder::Zstd(zstd::Encoder::new(file, LEVEL as i32)?),
+            _ => Encoder::Plain(file),
+        };
+        Ok(encoder)
+    }
+
+    /// Writes the trailer of the compression format and flushes the file
+    pub(crate) fn finish(self) -> Result<()> {
+        let mut file = match self {
+            Encoder::Plain(file) => file,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Bzip(encoder) => encoder.finish()?,
+            Encoder::Xz(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+impl Write for Encoder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::Plain(file) => file.write(buf),
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            Encoder::Bzip(encoder) => encoder.write(buf),
+            Encoder::Xz(encoder) => encoder.write(buf),
+            Encoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::Plain(file) => file.flush(),
+            Encoder::Gzip(encoder) => encoder.flush(),
+            Encoder::Bzip(encoder) => encoder.flush(),
+            Encoder::Xz(encoder) => encoder.flush(),
+            Encoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// The writer of a single output file
+pub(crate) type PartWriter = FastxWriter<Encoder>;
+
+/// How records are distributed between parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitMode {
+    /// Consecutive parts of at most this many records (or pairs)
+    Records(u64),
+    /// Consecutive parts which are closed once they hold at least this
+    /// many bases (of both mates for pairs)
+    Bases(u64),
+    /// This many parts which receive records (or pairs) in turn
+    RoundRobin(usize),
+}
+impl SplitMode {
+    /// # Usage
+    /// Returns the mode which splits an input with known [`Counts`]
+    /// into `n` consecutive parts of (nearly) equal numbers of records.
+    /// ```
+    /// use fxread::{count_records, SplitMode};
+    ///
+    /// let counts = count_records("example/sequences.fq").unwrap();
+    /// assert_eq!(SplitMode::parts(&counts, 3), SplitMode::Records(4));
+    /// ```
+    #[must_use]
+    pub fn parts(counts: &Counts, n: u64) -> Self {
+        SplitMode::Records(counts.records().div_ceil(n.max(1)).max(1))
+    }
+}
+
+/// Finishes and closes the writers of a part
+pub(crate) fn finish(writers: Vec<PartWriter>) -> Result<()> {
+    for writer in writers {
+        writer.into_inner().finish()?;
+    }
+    Ok(())
+}
+
+/// Creates a writer to a path compressed according to its extension
+pub(crate) fn create_writer(path: &Path) -> Result<PartWriter> {
+    Ok(FastxWriter::new(Encoder::create(path)?))
+}
+
+/// Splits records into multiple files.
+///
+/// Output paths are built from a template in which `{part}` is replaced
+/// by the 1-based part number (zero-padded to three digits) and, when
+/// splitting pairs, `{mate}` by 1 or 2. Parts are compressed according
+/// to their extension (`.gz`, `.bz2`, `.xz` or `.zst`) and records are
+/// written in the format they were read in.
+///
+/// ```
+/// use fxread::{initialize_reader, SplitMode, Splitter};
+///
+/// let template = std::env::temp_dir().join("fxread-doc-split.part_{part}.fq.gz");
+/// let splitter = Splitter::new(template.to_str().unwrap(), SplitMode::Records(4)).unwrap();
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let paths = splitter.split(reader).unwrap();
+/// assert_eq!(paths.len(), 3);
+/// assert!(paths[0].ends_with("fxread-doc-split.part_001.fq.gz"));
+/// # paths.iter().for_each(|p| std::fs::remove_file(p).unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Splitter {
+    template: String,
+    mode: SplitMode,
+}
+impl Splitter {
+    /// Creates a new [`Splitter`] writing parts to paths built from
+    /// `template`.
+    ///
+    /// Returns an error if the template has no `{part}` placeholder or
+    /// the mode has a size of zero.
+    pub fn new(template: &str, mode: SplitMode) -> Result<Self> {
+        if !template.contains("{part}") {
+            bail!("Split template must contain '{{part}}': {}", template);
+        }
+        if matches!(
+            mode,
+            SplitMode::Records(0) | SplitMode::Bases(0) | SplitMode::RoundRobin(0)
+        ) {
+            bail!("Split size must be greater than zero");
+        }
+        Ok(Self {
+            template: template.to_string(),
+            mode,
+        })
+    }
+
+    /// Returns the path of a part (0-based) and mate
+    fn path(&self, part: usize, mate: Option<usize>) -> PathBuf {
+        let mut path = self.template.replace("{part}", &format!("{:03}", part + 1));
+        if let Some(mate) = mate {
+            path = path.replace("{mate}", &mate.to_string());
+        }
+        PathBuf::from(path)
+    }
+
+    /// Opens the writers of every mate of a part
+    fn open(&self, part: usize, mates: usize) -> Result<Vec<(PathBuf, PartWriter)>> {
+        (0..mates)
+            .map(|mate| {
+                let path = self.path(part, (mates > 1).then_some(mate + 1));
+                let writer = create_writer(&path)?;
+                Ok((path, writer))
+            })
+            .collect()
+    }
+
+    /// Distributes the groups of mates returned by `next` and returns
+    /// the paths of each part
+    fn run<F>(&self, mates: usize, mut next: F) -> Result<Vec<Vec<PathBuf>>>
+    where
+        F: FnMut() -> Result<Option<Vec<Record>>>,
+    {
+        let mut paths: Vec<Vec<PathBuf>> = Vec::new();
+        // the writers of each part which may still receive records
+        let mut writers: Vec<Option<Vec<PartWriter>>> = Vec::new();
+        let mut records = 0;
+        let mut bases = 0;
+        let mut idx = 0;
+        while let Some(group) = next()? {
+            let part = match self.mode {
+                SplitMode::RoundRobin(n) => {
+                    idx += 1;
+                    (idx - 1) % n
+                }
+                SplitMode::Records(max) if records == max => {
+                    records = 0;
+                    paths.len()
+                }
+                SplitMode::Bases(max) if bases >= max => {
+                    bases = 0;
+                    paths.len()
+                }
+                _ => paths.len().saturating_sub(1),
+            };
+            if part == paths.len() {
+                // consecutive parts are finished before the next is opened
+                if !matches!(self.mode, SplitMode::RoundRobin(_)) {
+                    if let Some(previous) = writers.last_mut().and_then(Option::take) {
+                        finish(previous)?;
+                    }
+                }
+                let (files, opened) = self.open(part, mates)?.into_iter().unzip();
+                paths.push(files);
+                writers.push(Some(opened));
+            }
+            let part_writers = writers[part].as_mut().expect("part is open");
+            for (writer, record) in part_writers.iter_mut().zip(&group) {
+                writer.write_record(record)?;
+            }
+            records += 1;
+            bases += group.iter().map(|r| r.seq().len() as u64).sum::<u64>();
+        }
+        for part in writers.into_iter().flatten() {
+            finish(part)?;
+        }
+        Ok(paths)
+    }
+
+    /// Splits the records of `reader` and returns the paths of the parts
+    /// which were written.
+    pub fn split<R: FastxRead>(&self, mut reader: R) -> Result<Vec<PathBuf>> {
+        let paths = self.run(1, || Ok(reader.next_record()?.map(|r| vec![r])))?;
+        Ok(paths.into_iter().flatten().collect())
+    }
+
+    /// # Usage
+    /// Splits paired inputs so that every part of `r1` holds the mates
+    /// of the same part of `r2`.
+    ///
+    /// Returns an error if the template has no `{mate}` placeholder or
+    /// the inputs have different numbers of records.
+    /// ```
+    /// use fxread::{initialize_reader, SplitMode, Splitter};
+    ///
+    /// let template = std::env::temp_dir().join("fxread-doc-split.{part}_R{mate}.fq");
+    /// let splitter = Splitter::new(template.to_str().unwrap(), SplitMode::RoundRobin(2)).unwrap();
+    /// let r1 = initialize_reader("example/sequences.fq").unwrap();
+    /// let r2 = initialize_reader("example/sequences.fq.gz").unwrap();
+    /// let paths = splitter.split_paired(r1, r2).unwrap();
+    /// assert_eq!(paths.len(), 2);
+    /// assert!(paths[1].1.ends_with("fxread-doc-split.002_R2.fq"));
+    /// # paths.iter().for_each(|(a, b)| {
+    /// #     std::fs::remove_file(a).unwrap();
+    /// #     std::fs::remove_file(b).unwrap();
+    /// # });
+    /// ```
+    pub fn split_paired<R1, R2>(&self, mut r1: R1, mut r2: R2) -> Result<Vec<(PathBuf, PathBuf)>>
+    where
+        R1: FastxRead,
+        R2: FastxRead,
+    {
+        if !self.template.contains("{mate}") {
+            bail!(
+                "Paired split template must contain '{{mate}}': {}",
+                self.template
+            );
+        }
+        let paths = self.run(2, || {
+            Ok(next_pair(&mut r1, &mut r2)?.map(|(a, b)| vec![a, b]))
+        })?;
+        Ok(paths
+            .into_iter()
+            .map(|mut files| {
+                let r2 = files.pop().expect("two mates per part");
+                let r1 = files.pop().expect("two mates per part");
+                (r1, r2)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoder, SplitMode, Splitter};
+    use crate::{initialize_reader, FastaReader, Record};
+    use std::path::PathBuf;
+
+    const FASTA: &[u8] = b">a\nACGT\n>b\nAC\n>c\nA\n>d\nACGTACGT\n>e\nAC\n";
+
+    fn template(name: &str, pattern: &str) -> String {
+        let dir = std::env::temp_dir();
+        let name = format!("fxread-split-{}-{}.{}", std::process::id(), name, pattern);
+        dir.join(name).to_str().unwrap().to_string()
+    }
+
+    /// Reads back and removes the parts
+    fn read_parts(paths: &[PathBuf]) -> Vec<Vec<String>> {
+        paths
+            .iter()
+            .map(|path| {
+                let ids = initialize_reader(path)
+                    .unwrap()
+                    .map(|r: Record| r.id_str().to_string())
+                    .collect();
+                std::fs::remove_file(path).unwrap();
+                ids
+            })
+            .collect()
+    }
+
+    #[test]
+    fn by_records() {
+        let splitter =
+            Splitter::new(&template("records", "{part}.fa"), SplitMode::Records(2)).unwrap();
+        let paths = splitter.split(FastaReader::new(FASTA)).unwrap();
+        assert!(paths[2].to_str().unwrap().ends_with("records.003.fa"));
+        assert_eq!(
+            read_parts(&paths),
+            [vec!["a", "b"], vec!["c", "d"], vec!["e"]]
+        );
+    }
+
+    #[test]
+    fn by_bases() {
+        let splitter =
+            Splitter::new(&template("bases", "{part}.fa.gz"), SplitMode::Bases(5)).unwrap();
+        let paths = splitter.split(FastaReader::new(FASTA)).unwrap();
+        assert_eq!(
+            read_parts(&paths),
+            [vec!["a", "b"], vec!["c", "d"], vec!["e"]]
+        );
+    }
+
+    #[test]
+    fn round_robin() {
+        let splitter = Splitter::new(
+            &template("robin", "{part}.fa.zst"),
+            SplitMode::RoundRobin(2),
+        )
+        .unwrap();
+        let paths = splitter.split(FastaReader::new(FASTA)).unwrap();
+        assert_eq!(read_parts(&paths), [vec!["a", "c", "e"], vec!["b", "d"]]);
+
+        // fewer records than parts only creates the parts which are used
+        let splitter =
+            Splitter::new(&template("robin", "{part}.fa"), SplitMode::RoundRobin(10)).unwrap();
+        let input: &'static [u8] = b">a\nA\n";
+        let paths = splitter.split(FastaReader::new(input)).unwrap();
+        assert_eq!(read_parts(&paths), [vec!["a"]]);
+    }
+
+    #[test]
+    fn compressed_parts() {
+        for ext in ["gz", "bz2", "xz", "zst"] {
+            let pattern = format!("{{part}}.fq.{}", ext);
+            let splitter =
+                Splitter::new(&template("compressed", &pattern), SplitMode::Records(3)).unwrap();
+            let reader = initialize_reader("example/sequences.fq.gz").unwrap();
+            let paths = splitter.split(reader).unwrap();
+            let sizes: Vec<usize> = read_parts(&paths).iter().map(Vec::len).collect();
+            assert_eq!(sizes, [3, 3, 3, 1]);
+        }
+    }
+
+    #[test]
+    fn paired() {
+        let r1: &'static [u8] = b">p0/1\nAAA\n>p1/1\nAA\n>p2/1\nA\n";
+        let r2: &'static [u8] = b">p0/2\nCCC\n>p1/2\nCC\n>p2/2\nC\n";
+        let splitter =
+            Splitter::new(&template("paired", "{part}_{mate}.fa"), SplitMode::Bases(6)).unwrap();
+        let paths = splitter
+            .split_paired(FastaReader::new(r1), FastaReader::new(r2))
+            .unwrap();
+        let (first, second): (Vec<_>, Vec<_>) = paths.into_iter().unzip();
+        assert_eq!(read_parts(&first), [vec!["p0/1"], vec!["p1/1", "p2/1"]]);
+        assert_eq!(read_parts(&second), [vec!["p0/2"], vec!["p1/2", "p2/2"]]);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(Splitter::new("out.fa", SplitMode::Records(1)).is_err());
+        assert!(Splitter::new("out.{part}.fa", SplitMode::RoundRobin(0)).is_err());
+        let splitter = Splitter::new("out.{part}.fa", SplitMode::Records(1)).unwrap();
+        let input: &'static [u8] = b">a\nA\n";
+        assert!(splitter
+            .split_paired(FastaReader::new(input), FastaReader::new(input))
+            .is_err());
+        let path = std::path::PathBuf::from(template("bgzf", "fa.bgz"));
+        assert!(Encoder::create(&path).is_err());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn empty_input() {
+        let splitter =
+            Splitter::new(&template("empty", "{part}.fa"), SplitMode::Records(1)).unwrap();
+        let input: &'static [u8] = b"";
+        assert!(splitter.split(FastaReader::new(input)).unwrap().is_empty());
+    }
+}
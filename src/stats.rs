@@ -0,0 +1,316 @@
+use super::fastx::FastxRead;
+use super::record::Record;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// The encoding of quality scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityEncoding {
+    /// Sanger / Illumina 1.8+ ('!' is a quality of 0)
+    Phred33,
+    /// Illumina 1.3 to 1.7 ('@' is a quality of 0)
+    Phred64,
+}
+impl QualityEncoding {
+    /// Returns the character of a quality of 0
+    #[must_use]
+    pub fn offset(&self) -> u8 {
+        match self {
+            QualityEncoding::Phred33 => 33,
+            QualityEncoding::Phred64 => 64,
+        }
+    }
+}
+
+/// Summary statistics of a set of records.
+///
+/// Statistics are accumulated with [`Stats::add`] and partial results
+/// (e.g. of several threads or files) are combined with
+/// [`Stats::merge`]. Length distributions are kept as histograms so
+/// memory grows with the number of distinct lengths rather than records.
+///
+/// ```
+/// use fxread::{initialize_reader, QualityEncoding, Stats};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let stats = Stats::from_reader(reader).unwrap();
+/// assert_eq!(stats.records(), 10);
+/// assert!(stats.min_length().unwrap() <= stats.n50().unwrap());
+/// assert_eq!(stats.quality_encoding(), Some(QualityEncoding::Phred33));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    records: u64,
+    bases: u64,
+    /// The number of records of each length
+    lengths: BTreeMap<u64, u64>,
+    gc: u64,
+    n: u64,
+    /// The number of occurrences of each quality character (empty
+    /// until a record with qualities is added)
+    quals: Vec<u64>,
+}
+impl Stats {
+    /// Creates an empty [`Stats`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accumulates the statistics of every record of a reader
+    pub fn from_reader<R: FastxRead>(mut reader: R) -> Result<Self> {
+        let mut stats = Self::new();
+        while let Some(record) = reader.next_record()? {
+            stats.add(&record);
+        }
+        Ok(stats)
+    }
+
+    /// Adds a record to the statistics
+    pub fn add(&mut self, record: &Record) {
+        let seq = record.seq();
+        self.records += 1;
+        self.bases += seq.len() as u64;
+        *self.lengths.entry(seq.len() as u64).or_default() += 1;
+        for base in seq {
+            match base {
+                b'G' | b'C' | b'g' | b'c' | b'S' | b's' => self.gc += 1,
+                b'N' | b'n' => self.n += 1,
+                _ => {}
+            }
+        }
+        if let Some(qual) = record.qual() {
+            if self.quals.is_empty() {
+                self.quals = vec![0; 256];
+            }
+            qual.iter().for_each(|q| self.quals[*q as usize] += 1);
+        }
+    }
+
+    /// # Usage
+    /// Combines the statistics of another [`Stats`] into these
+    /// ```
+    /// use fxread::{initialize_reader, Stats};
+    ///
+    /// let mut stats = Stats::from_reader(initialize_reader("example/sequences.fa").unwrap()).unwrap();
+    /// let other = Stats::from_reader(initialize_reader("example/sequences.fa.gz").unwrap()).unwrap();
+    /// stats.merge(&other);
+    /// assert_eq!(stats.records(), 20);
+    /// assert_eq!(stats.n50(), other.n50());
+    /// ```
+    pub fn merge(&mut self, other: &Stats) {
+        self.records += other.records;
+        self.bases += other.bases;
+        for (length, count) in &other.lengths {
+            *self.lengths.entry(*length).or_default() += count;
+        }
+        self.gc += other.gc;
+        self.n += other.n;
+        if !other.quals.is_empty() {
+            if self.quals.is_empty() {
+                self.quals = vec![0; 256];
+            }
+            self.quals
+                .iter_mut()
+                .zip(&other.quals)
+                .for_each(|(a, b)| *a += b);
+        }
+    }
+
+    /// Returns the number of records
+    #[must_use]
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Returns the total number of bases
+    #[must_use]
+    pub fn bases(&self) -> u64 {
+        self.bases
+    }
+
+    /// Returns the length of the shortest record
+    #[must_use]
+    pub fn min_length(&self) -> Option<u64> {
+        self.lengths.keys().next().copied()
+    }
+
+    /// Returns the length of the longest record
+    #[must_use]
+    pub fn max_length(&self) -> Option<u64> {
+        self.lengths.keys().next_back().copied()
+    }
+
+    /// Returns the mean record length
+    #[must_use]
+    pub fn mean_length(&self) -> Option<f64> {
+        (self.records > 0).then(|| self.bases as f64 / self.records as f64)
+    }
+
+    /// Returns the length `Nx` and number of records `Lx` such that
+    /// records at least `Nx` long hold `fraction` of all bases
+    fn nx(&self, fraction: f64) -> Option<(u64, u64)> {
+        let target = self.bases as f64 * fraction;
+        let mut total = 0;
+        let mut records = 0;
+        for (length, count) in self.lengths.iter().rev() {
+            // the number of records of this length needed to reach the target
+            let needed = ((target - total as f64) / *length as f64).ceil().max(1.0) as u64;
+            if needed <= *count {
+                return Some((*length, records + needed));
+            }
+            total += length * count;
+            records += count;
+        }
+        None
+    }
+
+    /// Returns the N50: the length such that records of at least this
+    /// length hold half of all bases
+    #[must_use]
+    pub fn n50(&self) -> Option<u64> {
+        self.nx(0.5).map(|(length, _)| length)
+    }
+
+    /// Returns the N90: the length such that records of at least this
+    /// length hold 90% of all bases
+    #[must_use]
+    pub fn n90(&self) -> Option<u64> {
+        self.nx(0.9).map(|(length, _)| length)
+    }
+
+    /// Returns the L50: the smallest number of records which hold half
+    /// of all bases
+    #[must_use]
+    pub fn l50(&self) -> Option<u64> {
+        self.nx(0.5).map(|(_, records)| records)
+    }
+
+    /// Returns the fraction of bases which are G or C (or S)
+    #[must_use]
+    pub fn gc_content(&self) -> Option<f64> {
+        (self.bases > 0).then(|| self.gc as f64 / self.bases as f64)
+    }
+
+    /// Returns the fraction of bases which are N
+    #[must_use]
+    pub fn n_content(&self) -> Option<f64> {
+        (self.bases > 0).then(|| self.n as f64 / self.bases as f64)
+    }
+
+    /// Returns the detected encoding of the quality scores.
+    ///
+    /// Qualities are assumed to be Phred+64 only if none are below '@'
+    /// and some are above 'J' (the highest Phred+33 Illumina quality).
+    #[must_use]
+    pub fn quality_encoding(&self) -> Option<QualityEncoding> {
+        let min = self.quals.iter().position(|c| *c > 0)?;
+        let max = self.quals.iter().rposition(|c| *c > 0)?;
+        if min >= usize::from(b'@') && max > usize::from(b'J') {
+            Some(QualityEncoding::Phred64)
+        } else {
+            Some(QualityEncoding::Phred33)
+        }
+    }
+
+    /// Returns the fraction of quality scores of at least `min`
+    fn quality_fraction(&self, min: usize) -> Option<f64> {
+        let offset = usize::from(self.quality_encoding()?.offset());
+        let total: u64 = self.quals.iter().sum();
+        let passing: u64 = self.quals.iter().skip(offset + min).sum();
+        Some(passing as f64 / total as f64)
+    }
+
+    /// Returns the fraction of bases with a quality of at least 20
+    #[must_use]
+    pub fn q20(&self) -> Option<f64> {
+        self.quality_fraction(20)
+    }
+
+    /// Returns the fraction of bases with a quality of at least 30
+    #[must_use]
+    pub fn q30(&self) -> Option<f64> {
+        self.quality_fraction(30)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QualityEncoding, Stats};
+    use crate::{FastaReader, FastqReader, Record};
+
+    #[test]
+    fn fasta_stats() {
+        let input: &'static [u8] = b">a\nACGTN\n>b\nGG\n>c\nAAAAAAAA\n>d\nCC\n>e\nAT\n";
+        let stats = Stats::from_reader(FastaReader::new(input)).unwrap();
+        assert_eq!(stats.records(), 5);
+        assert_eq!(stats.bases(), 19);
+        assert_eq!((stats.min_length(), stats.max_length()), (Some(2), Some(8)));
+        assert_eq!(stats.mean_length(), Some(3.8));
+        assert_eq!(stats.gc_content(), Some(6.0 / 19.0));
+        assert_eq!(stats.n_content(), Some(1.0 / 19.0));
+        assert!(stats.quality_encoding().is_none());
+        assert!(stats.q20().is_none());
+    }
+
+    #[test]
+    fn nx() {
+        // lengths 8, 5, 2, 2, 2 over 19 bases
+        let input: &'static [u8] = b">a\nACGTN\n>b\nGG\n>c\nAAAAAAAA\n>d\nCC\n>e\nAT\n";
+        let stats = Stats::from_reader(FastaReader::new(input)).unwrap();
+        assert_eq!(stats.n50(), Some(5));
+        assert_eq!(stats.l50(), Some(2));
+        assert_eq!(stats.n90(), Some(2));
+
+        // ties within a length
+        let input: &'static [u8] = b">a\nAAAA\n>b\nAAAA\n>c\nAAAA\n>d\nAAAA\n";
+        let stats = Stats::from_reader(FastaReader::new(input)).unwrap();
+        assert_eq!((stats.n50(), stats.l50()), (Some(4), Some(2)));
+    }
+
+    #[test]
+    fn empty_stats() {
+        let stats = Stats::new();
+        assert!(stats.n50().is_none());
+        assert!(stats.mean_length().is_none());
+        assert!(stats.gc_content().is_none());
+        assert!(stats.min_length().is_none());
+    }
+
+    #[test]
+    fn quality_stats() {
+        // '5' = 20, '?' = 30, '+' = 10
+        let input: &'static [u8] = b"@a\nACGT\n+\n5?+?\n";
+        let stats = Stats::from_reader(FastqReader::new(input)).unwrap();
+        assert_eq!(stats.quality_encoding(), Some(QualityEncoding::Phred33));
+        assert_eq!(stats.q20(), Some(0.75));
+        assert_eq!(stats.q30(), Some(0.5));
+
+        // 'T' = 20, '^' = 30, 'h' = 40
+        let input: &'static [u8] = b"@a\nACGT\n+\nTT^h\n";
+        let stats = Stats::from_reader(FastqReader::new(input)).unwrap();
+        assert_eq!(stats.quality_encoding(), Some(QualityEncoding::Phred64));
+        assert_eq!(stats.q20(), Some(1.0));
+        assert_eq!(stats.q30(), Some(0.5));
+    }
+
+    #[test]
+    fn merge() {
+        let fasta = Record::new_fasta_from_parts(b"a", b"ACGT").unwrap();
+        let fastq = Record::new_fastq_from_parts(b"b", b"GG", b"II").unwrap();
+        let mut whole = Stats::new();
+        whole.add(&fasta);
+        whole.add(&fastq);
+
+        let mut first = Stats::new();
+        first.add(&fasta);
+        let mut second = Stats::new();
+        second.add(&fastq);
+        first.merge(&second);
+        assert_eq!(first, whole);
+
+        let mut empty = Stats::new();
+        empty.merge(&whole);
+        assert_eq!(empty, whole);
+    }
+}
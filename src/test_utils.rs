@@ -0,0 +1,23 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Writes a file to the temporary directory and returns its path.
+///
+/// Paths are unique per call so tests running in parallel can reuse
+/// file names.
+pub(crate) fn write_temp(name: &str, contents: &[u8]) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let idx = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let path = std::env::temp_dir().join(format!(
+        "fxread-test-{}-{}-{}",
+        std::process::id(),
+        idx,
+        name
+    ));
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(contents).unwrap();
+    path
+}
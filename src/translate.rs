@@ -0,0 +1,636 @@
+use super::record::Record;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::ops::Range;
+
+/// NCBI genetic code tables as `(id, name, amino acids)`.
+///
+/// Amino acids are listed by codon in `TCAG` order of the first,
+/// second and third base (i.e. `TTT`, `TTC`, `TTA`, `TTG`, `TCT`, ...).
+const TABLES: [(u8, &str, &[u8; 64]); 12] = [
+    (
+        1,
+        "Standard",
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        2,
+        "Vertebrate Mitochondrial",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+    ),
+    (
+        3,
+        "Yeast Mitochondrial",
+        b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        4,
+        "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        5,
+        "Invertebrate Mitochondrial",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+    ),
+    (
+        6,
+        "Ciliate, Dasycladacean and Hexamita Nuclear",
+        b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        9,
+        "Echinoderm and Flatworm Mitochondrial",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+    ),
+    (
+        10,
+        "Euplotid Nuclear",
+        b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        11,
+        "Bacterial, Archaeal and Plant Plastid",
+        b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        12,
+        "Alternative Yeast Nuclear",
+        b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+    ),
+    (
+        13,
+        "Ascidian Mitochondrial",
+        b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+    ),
+    (
+        14,
+        "Alternative Flatworm Mitochondrial",
+        b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+    ),
+];
+
+/// Amino acid used for codons containing ambiguous nucleotides
+const UNKNOWN: u8 = b'X';
+
+/// Amino acid used for stop codons
+const STOP: u8 = b'*';
+
+/// Amino acid at which open reading frames begin
+const START: u8 = b'M';
+
+/// Returns the index of a nucleotide in `TCAG` order.
+///
+/// Uracil is treated as thymine so RNA sequences translate as DNA.
+fn base_index(base: u8) -> Option<usize> {
+    match base {
+        b'T' | b't' | b'U' | b'u' => Some(0),
+        b'C' | b'c' => Some(1),
+        b'A' | b'a' => Some(2),
+        b'G' | b'g' => Some(3),
+        _ => None,
+    }
+}
+
+/// An NCBI genetic code used to translate codons into amino acids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeneticCode {
+    id: u8,
+    name: &'static str,
+    amino_acids: &'static [u8; 64],
+}
+impl GeneticCode {
+    /// # Usage
+    /// Selects a genetic code by its NCBI translation table number.
+    ///
+    /// Tables 1-6 and 9-14 are supported.
+    /// ```
+    /// let code = fxread::GeneticCode::from_id(2).unwrap();
+    /// assert_eq!(code.name(), "Vertebrate Mitochondrial");
+    /// assert!(fxread::GeneticCode::from_id(7).is_err());
+    /// ```
+    pub fn from_id(id: u8) -> Result<Self> {
+        match TABLES.iter().find(|(table, _, _)| *table == id) {
+            Some((id, name, amino_acids)) => Ok(Self {
+                id: *id,
+                name,
+                amino_acids,
+            }),
+            None => bail!("Unsupported genetic code table: {}", id),
+        }
+    }
+
+    /// The standard genetic code (NCBI table 1)
+    #[must_use]
+    pub fn standard() -> Self {
+        Self::from_id(1).expect("standard table is always present")
+    }
+
+    /// Returns all supported genetic codes
+    #[must_use]
+    pub fn all() -> Vec<Self> {
+        TABLES
+            .iter()
+            .map(|(id, name, amino_acids)| Self {
+                id: *id,
+                name,
+                amino_acids,
+            })
+            .collect()
+    }
+
+    /// Returns the NCBI translation table number
+    #[must_use]
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the NCBI name of the genetic code
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// # Usage
+    /// Translates a single codon into its amino acid.
+    ///
+    /// Codons containing ambiguous nucleotides translate to 'X'
+    /// and stop codons translate to '*'.
+    /// ```
+    /// let code = fxread::GeneticCode::standard();
+    /// assert_eq!(code.translate_codon(b"ATG"), b'M');
+    /// assert_eq!(code.translate_codon(b"tga"), b'*');
+    /// assert_eq!(code.translate_codon(b"ANG"), b'X');
+    /// ```
+    #[must_use]
+    pub fn translate_codon(&self, codon: &[u8]) -> u8 {
+        match codon {
+            [a, b, c] => self.lookup(base_index(*a), base_index(*b), base_index(*c)),
+            _ => UNKNOWN,
+        }
+    }
+
+    /// Translates the codon of three base indices
+    fn lookup(&self, a: Option<usize>, b: Option<usize>, c: Option<usize>) -> u8 {
+        match (a, b, c) {
+            (Some(a), Some(b), Some(c)) => self.amino_acids[a * 16 + b * 4 + c],
+            _ => UNKNOWN,
+        }
+    }
+}
+impl Default for GeneticCode {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+/// A reading frame of a nucleotide sequence.
+///
+/// Forward frames start at the first, second, or third base of the
+/// sequence and reverse frames start at the last, second to last, or
+/// third to last base of its reverse complement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Frame {
+    /// Forward strand starting at the first base
+    Plus1,
+    /// Forward strand starting at the second base
+    Plus2,
+    /// Forward strand starting at the third base
+    Plus3,
+    /// Reverse strand starting at the last base
+    Minus1,
+    /// Reverse strand starting at the second to last base
+    Minus2,
+    /// Reverse strand starting at the third to last base
+    Minus3,
+}
+impl Frame {
+    /// All six reading frames
+    pub const ALL: [Frame; 6] = [
+        Frame::Plus1,
+        Frame::Plus2,
+        Frame::Plus3,
+        Frame::Minus1,
+        Frame::Minus2,
+        Frame::Minus3,
+    ];
+
+    /// Returns the number of bases skipped before the first codon
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        match self {
+            Frame::Plus1 | Frame::Minus1 => 0,
+            Frame::Plus2 | Frame::Minus2 => 1,
+            Frame::Plus3 | Frame::Minus3 => 2,
+        }
+    }
+
+    /// Checks if the frame is read from the reverse complement
+    #[must_use]
+    pub fn is_reverse(&self) -> bool {
+        matches!(self, Frame::Minus1 | Frame::Minus2 | Frame::Minus3)
+    }
+
+    /// Returns the range of the sequence covered by the codon
+    /// at `index` of the frame
+    fn codon_range(&self, index: usize, len: usize) -> Range<usize> {
+        let start = self.offset() + 3 * index;
+        if self.is_reverse() {
+            len - start - 3..len - start
+        } else {
+            start..start + 3
+        }
+    }
+}
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_reverse() { '-' } else { '+' };
+        write!(f, "{}{}", sign, self.offset() + 1)
+    }
+}
+
+/// How stop codons are represented in translated sequences.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StopCodon {
+    /// Stop codons are translated as '*'
+    #[default]
+    Keep,
+    /// Translation ends at the first stop codon
+    Truncate,
+    /// Stop codons are omitted from the translation
+    Remove,
+}
+
+/// An open reading frame found by [`Translator::orfs`].
+#[derive(Debug, Clone)]
+pub struct Orf {
+    frame: Frame,
+    range: Range<usize>,
+    record: Record,
+}
+impl Orf {
+    /// Returns the frame the ORF was found in
+    #[must_use]
+    pub fn frame(&self) -> Frame {
+        self.frame
+    }
+
+    /// Returns the nucleotide range of the ORF (including its stop codon)
+    /// on the forward strand of the original sequence
+    #[must_use]
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Returns the translated protein [`Record`] of the ORF
+    #[must_use]
+    pub fn record(&self) -> &Record {
+        &self.record
+    }
+
+    /// Consumes the ORF and returns its protein [`Record`]
+    #[must_use]
+    pub fn into_record(self) -> Record {
+        self.record
+    }
+}
+
+/// Translates nucleotide records into protein records.
+///
+/// Translated records are Fasta records and codons containing ambiguous
+/// nucleotides are translated as 'X'. A trailing partial codon is ignored.
+/// The resulting sequences can be validated with
+/// [`Alphabet::protein`](crate::Alphabet::protein).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Translator {
+    code: GeneticCode,
+    stop_codon: StopCodon,
+}
+impl Translator {
+    /// # Usage
+    /// Creates a new [`Translator`] with the provided [`GeneticCode`]
+    /// ```
+    /// use fxread::{Frame, GeneticCode, Record, Translator};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"ATGAGATGA").unwrap();
+    /// let standard = Translator::new(GeneticCode::standard());
+    /// assert_eq!(standard.translate(&record, Frame::Plus1).unwrap().seq(), b"MR*");
+    ///
+    /// let mito = Translator::new(GeneticCode::from_id(2).unwrap());
+    /// assert_eq!(mito.translate(&record, Frame::Plus1).unwrap().seq(), b"M*W");
+    /// ```
+    #[must_use]
+    pub fn new(code: GeneticCode) -> Self {
+        Self {
+            code,
+            stop_codon: StopCodon::default(),
+        }
+    }
+
+    /// # Usage
+    /// Sets how stop codons are represented in translations
+    /// ```
+    /// use fxread::{Frame, Record, StopCodon, Translator};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"ATGTAAGGG").unwrap();
+    /// let translator = Translator::default().stop_codon(StopCodon::Remove);
+    /// assert_eq!(translator.translate(&record, Frame::Plus1).unwrap().seq(), b"MG");
+    /// ```
+    #[must_use]
+    pub fn stop_codon(mut self, stop_codon: StopCodon) -> Self {
+        self.stop_codon = stop_codon;
+        self
+    }
+
+    /// Returns the [`GeneticCode`] used for translation
+    #[must_use]
+    pub fn code(&self) -> GeneticCode {
+        self.code
+    }
+
+    /// # Usage
+    /// Translates a single frame of the record into a protein record
+    /// with the same identifier.
+    /// ```
+    /// use fxread::{Frame, Record, Translator};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"TTACATTCC").unwrap();
+    /// let translator = Translator::default();
+    /// assert_eq!(translator.translate(&record, Frame::Plus2).unwrap().seq(), b"YI");
+    /// assert_eq!(translator.translate(&record, Frame::Minus1).unwrap().seq(), b"GM*");
+    /// ```
+    pub fn translate(&self, record: &Record, frame: Frame) -> Result<Record> {
+        if record.empty() {
+            bail!("Cannot translate an empty record");
+        }
+        let protein = self.apply_stops(self.translate_frame(record.seq(), frame));
+        Record::new_fasta_from_parts(record.id(), &protein)
+    }
+
+    /// # Usage
+    /// Translates all six frames of the record into protein records.
+    ///
+    /// The frame is appended to the identifier of each record.
+    /// ```
+    /// use fxread::{Record, Translator};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"ATGGCC").unwrap();
+    /// let proteins = Translator::default().six_frame(&record).unwrap();
+    /// assert_eq!(proteins.len(), 6);
+    /// assert_eq!(proteins[0].id(), b"seq.0 frame=+1");
+    /// assert_eq!(proteins[0].seq(), b"MA");
+    /// assert_eq!(proteins[3].id(), b"seq.0 frame=-1");
+    /// assert_eq!(proteins[3].seq(), b"GH");
+    /// ```
+    pub fn six_frame(&self, record: &Record) -> Result<Vec<Record>> {
+        if record.empty() {
+            bail!("Cannot translate an empty record");
+        }
+        Frame::ALL
+            .iter()
+            .map(|frame| {
+                let protein = self.apply_stops(self.translate_frame(record.seq(), *frame));
+                Record::new_fasta_from_parts(&Self::tag(record.id(), *frame), &protein)
+            })
+            .collect()
+    }
+
+    /// # Usage
+    /// Finds the open reading frames of the record in all six frames.
+    ///
+    /// An ORF begins at a methionine codon and ends at the next stop codon
+    /// of its frame, and only ORFs with at least `min_length` amino acids
+    /// (excluding the stop) are reported. Methionines within an ORF do not
+    /// start a new ORF. The identifier of each record is tagged with the
+    /// frame and its 1-based inclusive coordinates on the forward strand.
+    /// ```
+    /// use fxread::{Frame, Record, Translator};
+    ///
+    /// let record = Record::new_fasta_from_parts(b"seq.0", b"CCATGAAATTTTAGCC").unwrap();
+    /// let orfs = Translator::default().orfs(&record, 2).unwrap();
+    /// assert_eq!(orfs.len(), 1);
+    /// assert_eq!(orfs[0].frame(), Frame::Plus3);
+    /// assert_eq!(orfs[0].range(), 2..14);
+    /// assert_eq!(orfs[0].record().seq(), b"MKF");
+    /// assert_eq!(orfs[0].record().id(), b"seq.0 frame=+3 start=3 end=14");
+    /// ```
+    pub fn orfs(&self, record: &Record, min_length: usize) -> Result<Vec<Orf>> {
+        if record.empty() {
+            bail!("Cannot translate an empty record");
+        }
+        let len = record.seq().len();
+        let mut orfs = Vec::new();
+        for frame in Frame::ALL {
+            let protein = self.translate_frame(record.seq(), frame);
+            let mut start = None;
+            for (idx, amino_acid) in protein.iter().enumerate() {
+                match (*amino_acid, start) {
+                    (START, None) => start = Some(idx),
+                    (STOP, Some(first)) => {
+                        start = None;
+                        if idx - first < min_length {
+                            continue;
+                        }
+                        let first_codon = frame.codon_range(first, len);
+                        let stop_codon = frame.codon_range(idx, len);
+                        let range = first_codon.start.min(stop_codon.start)
+                            ..first_codon.end.max(stop_codon.end);
+                        let mut id = Self::tag(record.id(), frame);
+                        id.extend_from_slice(
+                            format!(" start={} end={}", range.start + 1, range.end).as_bytes(),
+                        );
+                        orfs.push(Orf {
+                            frame,
+                            range,
+                            record: Record::new_fasta_from_parts(&id, &protein[first..idx])?,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(orfs)
+    }
+
+    /// Translates a frame of the sequence keeping stop codons
+    fn translate_frame(&self, seq: &[u8], frame: Frame) -> Vec<u8> {
+        let offset = frame.offset().min(seq.len());
+        if frame.is_reverse() {
+            // complementing a base index in TCAG order flips its second bit
+            let complement = |b: &u8| base_index(*b).map(|idx| idx ^ 2);
+            seq[..seq.len() - offset]
+                .rchunks_exact(3)
+                .map(|c| {
+                    self.code
+                        .lookup(complement(&c[2]), complement(&c[1]), complement(&c[0]))
+                })
+                .collect()
+        } else {
+            seq[offset..]
+                .chunks_exact(3)
+                .map(|c| self.code.translate_codon(c))
+                .collect()
+        }
+    }
+
+    /// Applies the stop codon handling to a translation
+    fn apply_stops(&self, mut protein: Vec<u8>) -> Vec<u8> {
+        match self.stop_codon {
+            StopCodon::Keep => {}
+            StopCodon::Truncate => {
+                if let Some(idx) = protein.iter().position(|c| *c == STOP) {
+                    protein.truncate(idx);
+                }
+            }
+            StopCodon::Remove => protein.retain(|c| *c != STOP),
+        }
+        protein
+    }
+
+    /// Appends the frame to a record identifier
+    fn tag(id: &[u8], frame: Frame) -> Vec<u8> {
+        let mut tagged = id.to_vec();
+        tagged.extend_from_slice(format!(" frame={}", frame).as_bytes());
+        tagged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frame, GeneticCode, StopCodon, Translator};
+    use crate::{Alphabet, Record};
+
+    #[test]
+    fn supported_tables() {
+        let ids: Vec<u8> = GeneticCode::all().iter().map(|c| c.id()).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14]);
+        assert!(GeneticCode::from_id(0).is_err());
+        assert!(GeneticCode::from_id(8).is_err());
+    }
+
+    #[test]
+    fn standard_codons() {
+        let code = GeneticCode::standard();
+        assert_eq!(code.translate_codon(b"TTT"), b'F');
+        assert_eq!(code.translate_codon(b"GGG"), b'G');
+        assert_eq!(code.translate_codon(b"TAA"), b'*');
+        assert_eq!(code.translate_codon(b"TAG"), b'*');
+        assert_eq!(code.translate_codon(b"TGA"), b'*');
+        assert_eq!(code.translate_codon(b"AUG"), b'M');
+        assert_eq!(code.translate_codon(b"ATR"), b'X');
+        assert_eq!(code.translate_codon(b"AT"), b'X');
+    }
+
+    #[test]
+    fn alternative_codes() {
+        let codon = |id, codon: &[u8]| GeneticCode::from_id(id).unwrap().translate_codon(codon);
+        assert_eq!(codon(2, b"AGA"), b'*');
+        assert_eq!(codon(3, b"CTG"), b'T');
+        assert_eq!(codon(4, b"TGA"), b'W');
+        assert_eq!(codon(5, b"AGG"), b'S');
+        assert_eq!(codon(6, b"TAA"), b'Q');
+        assert_eq!(codon(9, b"AAA"), b'N');
+        assert_eq!(codon(10, b"TGA"), b'C');
+        assert_eq!(codon(11, b"TGA"), b'*');
+        assert_eq!(codon(12, b"CTG"), b'S');
+        assert_eq!(codon(13, b"AGA"), b'G');
+        assert_eq!(codon(14, b"TAA"), b'Y');
+    }
+
+    #[test]
+    fn translate_reverse_frames() {
+        // reverse complement: CATGCCATT
+        let record = Record::new_fasta_from_parts(b"seq.0", b"AATGGCATG").unwrap();
+        let translator = Translator::default();
+        let frame = |f| translator.translate(&record, f).unwrap().seq().to_vec();
+        assert_eq!(frame(Frame::Minus1), b"HAI");
+        assert_eq!(frame(Frame::Minus2), b"MP");
+        assert_eq!(frame(Frame::Minus3), b"CH");
+    }
+
+    #[test]
+    fn translate_fastq_and_short() {
+        let record = Record::new_fastq_from_parts(b"seq.0", b"ATGNNNa", b"1234567").unwrap();
+        let protein = Translator::default()
+            .translate(&record, Frame::Plus1)
+            .unwrap();
+        assert!(protein.is_fasta());
+        assert_eq!(protein.seq(), b"MX");
+
+        let short = Record::new_fasta_from_parts(b"seq.1", b"A").unwrap();
+        let protein = Translator::default()
+            .translate(&short, Frame::Minus3)
+            .unwrap();
+        assert_eq!(protein.seq(), b"");
+    }
+
+    #[test]
+    fn translate_empty() {
+        assert!(Translator::default()
+            .translate(&Record::new(), Frame::Plus1)
+            .is_err());
+    }
+
+    #[test]
+    fn stop_handling() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ATGTAAGGGTGACCC").unwrap();
+        let translate = |stops| {
+            Translator::default()
+                .stop_codon(stops)
+                .translate(&record, Frame::Plus1)
+                .unwrap()
+                .seq()
+                .to_vec()
+        };
+        assert_eq!(translate(StopCodon::Keep), b"M*G*P");
+        assert_eq!(translate(StopCodon::Truncate), b"M");
+        assert_eq!(translate(StopCodon::Remove), b"MGP");
+    }
+
+    #[test]
+    fn six_frames_are_protein() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ATGCGTACGTTAGCNA").unwrap();
+        let proteins = Translator::default().six_frame(&record).unwrap();
+        let ids: Vec<&str> = proteins.iter().map(|r| r.id_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "seq.0 frame=+1",
+                "seq.0 frame=+2",
+                "seq.0 frame=+3",
+                "seq.0 frame=-1",
+                "seq.0 frame=-2",
+                "seq.0 frame=-3"
+            ]
+        );
+        assert!(proteins.iter().all(|r| r.valid_with(&Alphabet::protein())));
+    }
+
+    #[test]
+    fn reverse_orfs() {
+        // reverse complement: TTATGCCCTGAAA
+        let record = Record::new_fasta_from_parts(b"seq.0", b"TTTCAGGGCATAA").unwrap();
+        let orfs = Translator::default().orfs(&record, 1).unwrap();
+        assert_eq!(orfs.len(), 1);
+        assert_eq!(orfs[0].frame(), Frame::Minus3);
+        assert_eq!(orfs[0].range(), 2..11);
+        assert_eq!(orfs[0].record().seq(), b"MP");
+        assert_eq!(orfs[0].record().id(), b"seq.0 frame=-3 start=3 end=11");
+    }
+
+    #[test]
+    fn orf_min_length() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ATGTAAATGAAATAG").unwrap();
+        let orfs = Translator::default().orfs(&record, 1).unwrap();
+        assert_eq!(orfs.len(), 2);
+        let orfs = Translator::default().orfs(&record, 2).unwrap();
+        assert_eq!(orfs.len(), 1);
+        assert_eq!(orfs[0].record().seq(), b"MK");
+    }
+
+    #[test]
+    fn unterminated_orf() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ATGAAAAAA").unwrap();
+        assert!(Translator::default().orfs(&record, 1).unwrap().is_empty());
+    }
+}
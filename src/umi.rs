@@ -0,0 +1,460 @@
+use super::fastx::{FastxRead, Format};
+use super::record::Record;
+use super::sample::next_pair;
+use anyhow::{bail, Result};
+
+/// The layout of the cell barcode and UMI at the start of a read.
+///
+/// Each character describes one base: `C` for the cell barcode, `N`
+/// for the UMI and `X` for bases which are discarded (e.g. linkers).
+/// All bases of the pattern are trimmed from the read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UmiPattern {
+    pattern: Vec<u8>,
+}
+impl UmiPattern {
+    /// # Usage
+    /// Parses a pattern such as `CCCCCCCCCCCCCCCCNNNNNNNNNNNN` (a 10x
+    /// v3 read 1)
+    /// ```
+    /// use fxread::UmiPattern;
+    ///
+    /// let pattern = UmiPattern::new("CCCCXXNNNN").unwrap();
+    /// assert_eq!(pattern.len(), 10);
+    /// assert!(UmiPattern::new("CCCCZZNN").is_err());
+    /// ```
+    pub fn new(pattern: &str) -> Result<Self> {
+        if let Some(c) = pattern.chars().find(|c| !matches!(c, 'C' | 'N' | 'X')) {
+            bail!("Invalid character in UMI pattern: '{}'", c);
+        }
+        if !pattern.contains(['C', 'N']) {
+            bail!("UMI pattern must contain a cell barcode (C) or UMI (N) base");
+        }
+        Ok(Self {
+            pattern: pattern.as_bytes().to_vec(),
+        })
+    }
+
+    /// Returns the number of bases covered by the pattern
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pattern.len()
+    }
+
+    /// Returns whether the pattern is empty (never true)
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_empty()
+    }
+}
+
+/// How extracted barcodes are written into headers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    /// Appends the cell barcode and UMI to the read name joined by this
+    /// separator (e.g. `read.0_CELL_UMI 1:N:0`), as used by UMI-tools
+    Suffix(char),
+    /// Appends `CB:Z:<cell>` and `UB:Z:<umi>` tags to the comment
+    Tags,
+}
+impl Default for HeaderFormat {
+    fn default() -> Self {
+        HeaderFormat::Suffix('_')
+    }
+}
+
+/// The mates of a pair whose headers are annotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnnotateMates {
+    /// Only the read the barcodes were extracted from
+    First,
+    /// Only the other read of the pair
+    Second,
+    /// Both reads of the pair
+    #[default]
+    Both,
+}
+
+/// The cell barcode and UMI extracted from a read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Barcodes {
+    cell: Vec<u8>,
+    umi: Vec<u8>,
+}
+impl Barcodes {
+    /// Returns the cell barcode (empty without `C` bases in the pattern)
+    #[must_use]
+    pub fn cell(&self) -> &[u8] {
+        &self.cell
+    }
+
+    /// Returns the UMI (empty without `N` bases in the pattern)
+    #[must_use]
+    pub fn umi(&self) -> &[u8] {
+        &self.umi
+    }
+}
+
+/// Moves cell barcodes and UMIs from the start of reads into headers.
+///
+/// ```
+/// use fxread::{HeaderFormat, Record, UmiExtractor, UmiPattern};
+///
+/// let pattern = UmiPattern::new("CCCCNNN").unwrap();
+/// let extractor = UmiExtractor::new(pattern).format(HeaderFormat::Tags);
+///
+/// let mut record = Record::new_fastq_from_parts(b"r0 1:N:0", b"ACGTTTGCAT", b"IIIIIII#!5").unwrap();
+/// let barcodes = extractor.extract(&mut record).unwrap();
+/// assert_eq!(barcodes.umi(), b"TTG");
+/// assert_eq!(record.id(), b"r0 1:N:0 CB:Z:ACGT UB:Z:TTG");
+/// assert_eq!(record.seq(), b"CAT");
+/// assert_eq!(record.qual().unwrap(), b"#!5");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UmiExtractor {
+    pattern: UmiPattern,
+    format: HeaderFormat,
+    mates: AnnotateMates,
+}
+impl UmiExtractor {
+    /// Creates a new [`UmiExtractor`] appending barcodes to read names
+    /// with '_' and annotating both mates of pairs
+    #[must_use]
+    pub fn new(pattern: UmiPattern) -> Self {
+        Self {
+            pattern,
+            format: HeaderFormat::default(),
+            mates: AnnotateMates::default(),
+        }
+    }
+
+    /// Sets how barcodes are written into headers
+    #[must_use]
+    pub fn format(mut self, format: HeaderFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets which mates of a pair have their headers annotated
+    #[must_use]
+    pub fn mates(mut self, mates: AnnotateMates) -> Self {
+        self.mates = mates;
+        self
+    }
+
+    /// Reads the barcodes at the start of a read and trims them
+    fn cut(&self, record: &mut Record) -> Result<Barcodes> {
+        let seq = record.seq();
+        if seq.len() < self.pattern.len() {
+            bail!(
+                "Record is shorter ({}) than the UMI pattern ({})",
+                seq.len(),
+                self.pattern.len()
+            );
+        }
+        let mut barcodes = Barcodes {
+            cell: Vec::new(),
+            umi: Vec::new(),
+        };
+        for (kind, base) in self.pattern.pattern.iter().zip(seq) {
+            match kind {
+                b'C' => barcodes.cell.push(*base),
+                b'N' => barcodes.umi.push(*base),
+                _ => {}
+            }
+        }
+        record.trim_left(self.pattern.len())?;
+        Ok(barcodes)
+    }
+
+    /// Writes the barcodes into the header of a read
+    fn annotate(&self, record: &mut Record, barcodes: &Barcodes) -> Result<()> {
+        let header = record.id();
+        let mut id =
+            Vec::with_capacity(header.len() + barcodes.cell.len() + barcodes.umi.len() + 12);
+        match self.format {
+            HeaderFormat::Suffix(separator) => {
+                let name_end = header
+                    .iter()
+                    .position(u8::is_ascii_whitespace)
+                    .unwrap_or(header.len());
+                id.extend_from_slice(&header[..name_end]);
+                let mut buffer = [0; 4];
+                let separator = separator.encode_utf8(&mut buffer).as_bytes();
+                for part in [&barcodes.cell, &barcodes.umi] {
+                    if !part.is_empty() {
+                        id.extend_from_slice(separator);
+                        id.extend_from_slice(part);
+                    }
+                }
+                id.extend_from_slice(&header[name_end..]);
+            }
+            HeaderFormat::Tags => {
+                id.extend_from_slice(header);
+                for (tag, part) in [(b"CB:Z:", &barcodes.cell), (b"UB:Z:", &barcodes.umi)] {
+                    if !part.is_empty() {
+                        id.push(b' ');
+                        id.extend_from_slice(tag);
+                        id.extend_from_slice(part);
+                    }
+                }
+            }
+        }
+        record.set_id(&id)
+    }
+
+    /// Extracts the barcodes of a read, trims them from its sequence and
+    /// writes them into its header.
+    ///
+    /// Returns an error if the read is shorter than the pattern.
+    pub fn extract(&self, record: &mut Record) -> Result<Barcodes> {
+        let barcodes = self.cut(record)?;
+        self.annotate(record, &barcodes)?;
+        Ok(barcodes)
+    }
+
+    /// # Usage
+    /// Extracts the barcodes of the first read of a pair and writes
+    /// them into the headers of the configured mates.
+    ///
+    /// Returns an error if the first read is shorter than the pattern.
+    /// ```
+    /// use fxread::{AnnotateMates, Record, UmiExtractor, UmiPattern};
+    ///
+    /// let extractor = UmiExtractor::new(UmiPattern::new("CCNN").unwrap()).mates(AnnotateMates::Second);
+    /// let mut r1 = Record::new_fasta_from_parts(b"r0", b"ACGTAA").unwrap();
+    /// let mut r2 = Record::new_fasta_from_parts(b"r0", b"GGGG").unwrap();
+    /// extractor.extract_pair(&mut r1, &mut r2).unwrap();
+    /// assert_eq!((r1.id(), r1.seq()), (b"r0".as_slice(), b"AA".as_slice()));
+    /// assert_eq!(r2.id(), b"r0_AC_GT");
+    /// ```
+    pub fn extract_pair(&self, r1: &mut Record, r2: &mut Record) -> Result<Barcodes> {
+        let barcodes = self.cut(r1)?;
+        if matches!(self.mates, AnnotateMates::First | AnnotateMates::Both) {
+            self.annotate(r1, &barcodes)?;
+        }
+        if matches!(self.mates, AnnotateMates::Second | AnnotateMates::Both) {
+            self.annotate(r2, &barcodes)?;
+        }
+        Ok(barcodes)
+    }
+}
+
+/// A reader adaptor which extracts barcodes from every record with an
+/// [`UmiExtractor`].
+///
+/// Records shorter than the pattern are skipped and counted.
+///
+/// ```
+/// use fxread::{initialize_reader, UmiExtractor, UmiPattern, UmiReader};
+///
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let extractor = UmiExtractor::new(UmiPattern::new("CCCCCCCCNNNNNNNN").unwrap());
+/// let mut umis = UmiReader::new(reader, extractor);
+/// let record = umis.next().unwrap();
+/// assert_eq!(record.id_str().split('_').count(), 3);
+/// assert_eq!(umis.skipped(), 0);
+/// ```
+pub struct UmiReader<R: FastxRead> {
+    reader: R,
+    extractor: UmiExtractor,
+    skipped: u64,
+}
+impl<R: FastxRead> UmiReader<R> {
+    /// Creates a new [`UmiReader`]
+    pub fn new(reader: R, extractor: UmiExtractor) -> Self {
+        Self {
+            reader,
+            extractor,
+            skipped: 0,
+        }
+    }
+
+    /// Returns the number of records skipped for being shorter than the
+    /// pattern
+    #[must_use]
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl<R: FastxRead> FastxRead for UmiReader<R> {
+    fn next_record(&mut self) -> Result<Option<Record>> {
+        while let Some(mut record) = self.reader.next_record()? {
+            if record.seq().len() < self.extractor.pattern.len() {
+                self.skipped += 1;
+                continue;
+            }
+            self.extractor.extract(&mut record)?;
+            return Ok(Some(record));
+        }
+        Ok(None)
+    }
+
+    fn format(&self) -> Option<Format> {
+        self.reader.format()
+    }
+}
+
+impl<R: FastxRead> Iterator for UmiReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_record() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+/// A reader adaptor which extracts barcodes from the first read of
+/// every pair with an [`UmiExtractor`].
+///
+/// Pairs whose first read is shorter than the pattern are skipped and
+/// counted.
+pub struct PairedUmiReader<R1: FastxRead, R2: FastxRead> {
+    r1: R1,
+    r2: R2,
+    extractor: UmiExtractor,
+    skipped: u64,
+}
+impl<R1: FastxRead, R2: FastxRead> PairedUmiReader<R1, R2> {
+    /// Creates a new [`PairedUmiReader`]
+    pub fn new(r1: R1, r2: R2, extractor: UmiExtractor) -> Self {
+        Self {
+            r1,
+            r2,
+            extractor,
+            skipped: 0,
+        }
+    }
+
+    /// Returns the number of pairs skipped for having a first read
+    /// shorter than the pattern
+    #[must_use]
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the next pair of records with extracted barcodes.
+    ///
+    /// Returns an error if the inputs have different numbers of records.
+    pub fn next_pair(&mut self) -> Result<Option<(Record, Record)>> {
+        while let Some((mut a, mut b)) = next_pair(&mut self.r1, &mut self.r2)? {
+            if a.seq().len() < self.extractor.pattern.len() {
+                self.skipped += 1;
+                continue;
+            }
+            self.extractor.extract_pair(&mut a, &mut b)?;
+            return Ok(Some((a, b)));
+        }
+        Ok(None)
+    }
+}
+
+impl<R1: FastxRead, R2: FastxRead> Iterator for PairedUmiReader<R1, R2> {
+    type Item = (Record, Record);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_pair() {
+            Ok(r) => r,
+            Err(why) => panic!("{}", why),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AnnotateMates, HeaderFormat, PairedUmiReader, UmiExtractor, UmiPattern, UmiReader,
+    };
+    use crate::{FastqReader, FastxRead, FastxWriter, Record, Strictness};
+
+    fn extractor(pattern: &str) -> UmiExtractor {
+        UmiExtractor::new(UmiPattern::new(pattern).unwrap())
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(UmiPattern::new("").is_err());
+        assert!(UmiPattern::new("XXXX").is_err());
+        assert!(UmiPattern::new("cccc").is_err());
+        assert!(UmiPattern::new("NNNN").is_ok());
+    }
+
+    #[test]
+    fn suffix_keeps_comment() {
+        let mut record = Record::new_fasta_from_parts(b"r0 1:N:0:ACGT", b"AACCGGTT").unwrap();
+        let barcodes = extractor("CCXXNN").extract(&mut record).unwrap();
+        assert_eq!(barcodes.cell(), b"AA");
+        assert_eq!(barcodes.umi(), b"GG");
+        assert_eq!(record.id(), b"r0_AA_GG 1:N:0:ACGT");
+        assert_eq!(record.seq(), b"TT");
+    }
+
+    #[test]
+    fn umi_only() {
+        let mut record = Record::new_fasta_from_parts(b"r0", b"ACGTAA").unwrap();
+        let extractor = extractor("NNNN").format(HeaderFormat::Suffix(':'));
+        extractor.extract(&mut record).unwrap();
+        assert_eq!(record.id(), b"r0:ACGT");
+
+        let mut record = Record::new_fasta_from_parts(b"r0", b"ACGTAA").unwrap();
+        let extractor = self::extractor("NNNN").format(HeaderFormat::Tags);
+        extractor.extract(&mut record).unwrap();
+        assert_eq!(record.id(), b"r0 UB:Z:ACGT");
+    }
+
+    #[test]
+    fn short_reads() {
+        let mut record = Record::new_fasta_from_parts(b"r0", b"ACG").unwrap();
+        assert!(extractor("CCNN").extract(&mut record).is_err());
+
+        let input: &'static [u8] = b"@r0\nACG\n+\nIII\n@r1\nACGTA\n+\nIIIII\n";
+        let mut reader = UmiReader::new(FastqReader::new(input), extractor("CCNN"));
+        let record = reader.next().unwrap();
+        assert_eq!(record.id(), b"r1_AC_GT");
+        assert_eq!(record.qual().unwrap(), b"I");
+        assert!(reader.next().is_none());
+        assert_eq!(reader.skipped(), 1);
+    }
+
+    #[test]
+    fn paired_reads() {
+        let r1: &'static [u8] = b"@p0 1:N\nACGTAA\n+\nIIIIII\n@p1 1:N\nAC\n+\nII\n";
+        let r2: &'static [u8] = b"@p0 2:N\nGGGG\n+\nIIII\n@p1 2:N\nCCCC\n+\nIIII\n";
+        let mut reader = PairedUmiReader::new(
+            FastqReader::new(r1),
+            FastqReader::new(r2),
+            extractor("CCNN").mates(AnnotateMates::Both),
+        );
+        let (a, b) = reader.next_pair().unwrap().unwrap();
+        assert_eq!(a.id(), b"p0_AC_GT 1:N");
+        assert_eq!(b.id(), b"p0_AC_GT 2:N");
+        assert_eq!(b.seq(), b"GGGG");
+        assert!(reader.next_pair().unwrap().is_none());
+        assert_eq!(reader.skipped(), 1);
+
+        let mut a = Record::new_fasta_from_parts(b"p0", b"ACGTAA").unwrap();
+        let mut b = Record::new_fasta_from_parts(b"p0", b"GGGG").unwrap();
+        let extractor = extractor("CCNN").mates(AnnotateMates::First);
+        extractor.extract_pair(&mut a, &mut b).unwrap();
+        assert_eq!((a.id(), b.id()), (b"p0_AC_GT".as_slice(), b"p0".as_slice()));
+    }
+
+    #[test]
+    fn annotated_round_trip() {
+        let input: &'static [u8] = b"@r0 1:N\nACGTAA\n+r0 1:N\nIIIIII\n";
+        let mut record = FastqReader::new(input).next_record().unwrap().unwrap();
+        extractor("CCNN").extract(&mut record).unwrap();
+        let mut writer = FastxWriter::new(Vec::new());
+        writer.write_record(&record).unwrap();
+        let output = writer.into_inner();
+        assert_eq!(output, b"@r0_AC_GT 1:N\nAA\n+\nII\n");
+
+        let mut reader = FastqReader::with_strictness(output.as_slice(), Strictness::Standard);
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!(record.id(), b"r0_AC_GT 1:N");
+        assert!(reader.next_record().unwrap().is_none());
+    }
+}
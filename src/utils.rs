@@ -6,19 +6,11 @@ use std::{
     path::Path,
 };
 
-use super::{FastaReader, FastqReader, FastxRead, Record};
+use super::parse::skip_preamble;
+use super::{FastaReader, FastqReader, FastxRead, MmapFile, MmapReader, ReaderBuilder, Record};
 
 const BUFFER_SIZE: usize = 4096 * 68;
 
-fn initialize_generic_buffer<P>(path: P) -> Result<Box<BufReader<Box<dyn std::io::Read>>>>
-where
-    P: AsRef<Path>,
-{
-    Ok(Box::new(std::io::BufReader::new(
-        niffler::get_reader(Box::new(File::open(path)?))?.0,
-    )))
-}
-
 fn initialize_generic_reader(
     buffer: Box<dyn BufRead>,
     is_fasta: bool,
@@ -30,12 +22,16 @@ fn initialize_generic_reader(
     }
 }
 
-/// # Initializing a reader dependent on the file path extensions.
-/// ## Recognized Extensions
-/// This recognizes `FASTA` formats from `*.fa` and `*.fasta` and
-/// `FASTQ` formats from `*.fq` and `*.fastq`. These will then be
-/// opened with gzip buffers or standard depending on if the
-/// `*.gz` extension is found at the end of the pathname.
+/// # Initializing a reader dependent on the file contents.
+/// ## Detection
+/// The compression (gzip, bzip2, xz, or zstd) is detected from the
+/// leading bytes of the file and the format from the first character
+/// of the first record ('>' for `FASTA` and '@' for `FASTQ`). File
+/// extensions are not considered. Use a [`ReaderBuilder`] to provide
+/// the format or compression explicitly.
+///
+/// An input without any records is an error. Use
+/// [`ReaderBuilder::allow_empty`] to read it as an empty iterator instead.
 ///
 /// ## From Fasta
 /// This example shows the creation of a reader from a fasta formatted
@@ -52,7 +48,7 @@ fn initialize_generic_reader(
 /// A common problem for the above however is that your file
 /// may actually be gzipped. Here you can use the same function
 /// and it will handle the initialization of the reader depending
-/// on the extension.
+/// on the detected compression.
 /// ```
 /// use fxread::initialize_reader;
 /// let path = "example/sequences.fa.gz";
@@ -82,20 +78,42 @@ fn initialize_generic_reader(
 /// reader
 ///     .for_each(|record| println!("{:?}", record));
 /// ```
-pub fn initialize_reader<P>(path: P) -> Result<Box<dyn FastxRead<Item = Record>>>
+///
+/// ## Sending across threads
+/// The returned reader is [`Send`] so it can be moved into another
+/// thread or handed to a parallel iterator.
+/// ```
+/// use fxread::initialize_reader;
+/// let reader = initialize_reader("example/sequences.fq").unwrap();
+/// let handle = std::thread::spawn(move || reader.count());
+/// assert_eq!(handle.join().unwrap(), 10);
+/// ```
+pub fn initialize_reader<P>(path: P) -> Result<Box<dyn FastxRead<Item = Record> + Send>>
 where
     P: AsRef<Path>,
 {
-    let mut buffer = initialize_generic_buffer(path)?;
-    buffer.fill_buf()?;
-    if buffer.buffer().is_empty() {
-        return Err(anyhow::anyhow!("No data in input file"));
-    }
-    match buffer.buffer()[0] {
-        b'>' => Ok(initialize_generic_reader(buffer, true)),
-        b'@' => Ok(initialize_generic_reader(buffer, false)),
-        _ => Err(anyhow::anyhow!("Unrecognized file format")),
-    }
+    ReaderBuilder::new().open(path)
+}
+
+/// Initializes a memory mapped reader (see [`MmapReader`]) from an
+/// uncompressed file path.
+///
+/// ```
+/// use fxread::initialize_mmap_reader;
+/// let path = "example/sequences.fq";
+/// // SAFETY: the example file is not modified while it is mapped
+/// let reader = unsafe { initialize_mmap_reader(path) }.unwrap();
+/// assert_eq!(reader.count(), 10);
+/// ```
+///
+/// # Safety
+/// The file must not be truncated or modified while the reader is alive.
+pub unsafe fn initialize_mmap_reader<P>(path: P) -> Result<Box<dyn FastxRead<Item = Record> + Send>>
+where
+    P: AsRef<Path>,
+{
+    let file = MmapFile::from_file(&File::open(path)?)?;
+    Ok(Box::new(MmapReader::new(file)))
 }
 
 /// Initializes a reader from stdin. This is useful for piping
@@ -114,6 +132,7 @@ pub fn initialize_stdin_reader<R: BufRead + 'static>(
     reader: R,
 ) -> Result<Box<dyn FastxRead<Item = Record>>> {
     let mut buffer = BufReader::with_capacity(BUFFER_SIZE, reader);
+    skip_preamble(&mut buffer)?;
     buffer.fill_buf()?;
     if buffer.buffer().is_empty() {
         return Err(anyhow::anyhow!("No data in stdin"));
@@ -211,6 +230,20 @@ mod test {
         assert_eq!(num_records, 10);
     }
 
+    #[test]
+    fn assign_mmap_fasta() {
+        let path = "example/sequences.fa";
+        let reader = unsafe { initialize_mmap_reader(path) }.expect("invalid path");
+        let num_records = reader.into_iter().map(|x| assert!(!x.empty())).count();
+        assert_eq!(num_records, 10);
+    }
+
+    #[test]
+    fn assign_mmap_compressed() {
+        let path = "example/sequences.fa.gz";
+        assert!(unsafe { initialize_mmap_reader(path) }.is_err());
+    }
+
     #[test]
     fn assign_fa_stdin() {
         let example_fa = ">test\nACGT\n>test2\nACGT\n";
@@ -229,6 +262,14 @@ mod test {
         assert_eq!(num_records, 2);
     }
 
+    #[test]
+    fn assign_stdin_with_preamble() {
+        let example_fa = "\u{feff}# comment\n\n>test\nACGT\n";
+        let cursor = Cursor::new(example_fa);
+        let reader = initialize_stdin_reader(cursor).expect("invalid path");
+        assert_eq!(reader.count(), 1);
+    }
+
     #[test]
     fn assign_malformed_stdin() {
         let example_malformed = "test\nACGT\n+\n!!!!\n@test2\nACGT\n+\n!!!!\n";
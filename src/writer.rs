@@ -0,0 +1,141 @@
+use super::record::Record;
+use anyhow::Result;
+use memchr::memchr_iter;
+use std::io::Write;
+
+/// The line terminator used when writing records.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Unix line endings ("\n")
+    #[default]
+    Lf,
+    /// Windows line endings ("\r\n")
+    Crlf,
+}
+
+/// A Fasta and Fastq Writer implementation.
+///
+/// Records are written in the format they were read or created in.
+pub struct FastxWriter<W: Write> {
+    writer: W,
+    line_ending: LineEnding,
+}
+impl<W: Write> FastxWriter<W> {
+    /// # Example
+    /// Creates a new [`FastxWriter`] explicitly from an object
+    /// which implements [`Write`].
+    ///
+    /// ```
+    /// let record = fxread::Record::new_fasta_from_parts(b"seq.0", b"ACGT").unwrap();
+    /// let mut writer = fxread::FastxWriter::new(Vec::new());
+    /// writer.write_record(&record).unwrap();
+    /// assert_eq!(writer.into_inner(), b">seq.0\nACGT\n");
+    /// ```
+    pub fn new(writer: W) -> Self {
+        Self::with_line_ending(writer, LineEnding::default())
+    }
+
+    /// # Example
+    /// Creates a new [`FastxWriter`] which terminates lines
+    /// with the provided [`LineEnding`].
+    ///
+    /// ```
+    /// use fxread::{FastxWriter, LineEnding, Record};
+    ///
+    /// let record = Record::new_fastq_from_parts(b"seq.0", b"ACGT", b"1234").unwrap();
+    /// let mut writer = FastxWriter::with_line_ending(Vec::new(), LineEnding::Crlf);
+    /// writer.write_record(&record).unwrap();
+    /// assert_eq!(writer.into_inner(), b"@seq.0\r\nACGT\r\n+\r\n1234\r\n");
+    /// ```
+    pub fn with_line_ending(writer: W, line_ending: LineEnding) -> Self {
+        Self {
+            writer,
+            line_ending,
+        }
+    }
+
+    /// Returns the [`LineEnding`] used by the writer
+    #[must_use]
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Writes a single [`Record`]
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        let data = record.data();
+        match self.line_ending {
+            LineEnding::Lf => self.writer.write_all(data)?,
+            LineEnding::Crlf => {
+                let mut start = 0;
+                for idx in memchr_iter(b'\n', data) {
+                    self.writer.write_all(&data[start..idx])?;
+                    self.writer.write_all(b"\r\n")?;
+                    start = idx + 1;
+                }
+                self.writer.write_all(&data[start..])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every [`Record`] of an iterator
+    pub fn write_all<I>(&mut self, records: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Record>,
+    {
+        for record in records {
+            self.write_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FastxWriter, LineEnding};
+    use crate::{FastaReader, FastqReader, Record};
+
+    #[test]
+    fn write_fasta() {
+        let record = Record::new_fasta_from_parts(b"seq.0", b"ACGT").unwrap();
+        let mut writer = FastxWriter::new(Vec::new());
+        writer.write_record(&record).unwrap();
+        assert_eq!(writer.line_ending(), LineEnding::Lf);
+        assert_eq!(writer.into_inner(), b">seq.0\nACGT\n");
+    }
+
+    #[test]
+    fn write_fastq_crlf() {
+        let record = Record::new_fastq_from_parts(b"seq.0", b"ACGT", b"1234").unwrap();
+        let mut writer = FastxWriter::with_line_ending(Vec::new(), LineEnding::Crlf);
+        writer.write_record(&record).unwrap();
+        assert_eq!(writer.into_inner(), b"@seq.0\r\nACGT\r\n+\r\n1234\r\n");
+    }
+
+    #[test]
+    fn round_trip_fastq() {
+        let fastq: &'static [u8] = b"@seq.0\nACGT\n+\n1234\n@seq.1\nTTTT\n+\n5678\n";
+        let mut writer = FastxWriter::new(Vec::new());
+        writer.write_all(FastqReader::new(fastq)).unwrap();
+        assert_eq!(writer.into_inner(), fastq);
+    }
+
+    #[test]
+    fn round_trip_crlf() {
+        let fasta: &'static [u8] = b">seq.0\r\nACGT\r\n>seq.1\r\nTTTT\r\n";
+        let mut writer = FastxWriter::with_line_ending(Vec::new(), LineEnding::Crlf);
+        writer.write_all(FastaReader::new(fasta)).unwrap();
+        assert_eq!(writer.into_inner(), fasta);
+    }
+}